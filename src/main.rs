@@ -4,16 +4,26 @@ mod setup;
 
 use crate::camera::OrbitCameraPlugin;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, render::mesh::skinning::SkinnedMesh, window::PrimaryWindow};
 use physics::{
-    collider::{Collider, Shape},
-    motion::{Angular, Linear, Rigid},
-    PhysicsParameters, PhysicsPlugin,
+    character::{CharacterController, CharacterInput},
+    collider::{Collider, Sensor, Shape},
+    force::{ApplyImpulseExt, Explosion},
+    joint::SpringJoint,
+    motion::{Angular, Ccd, Linear, LockedAxes, Rigid, RigidBody},
+    pairs::{CollisionEnded, CollisionStarted, Collisions, TriggerEnter, TriggerExit},
+    query::{QueryFilter, SpatialQuery},
+    ragdoll::{Ragdoll, RagdollBuilder, RagdollMode},
+    vehicle::{Vehicle, VehicleInput, Wheel},
+    PhysicsParameters, PhysicsPlugin, PhysicsSet,
 };
 use rand::random;
 
 pub const CAMERA_DISTANCE: f32 = 20.0;
 
+#[derive(Component)]
+struct Platform;
+
 fn main() {
     let mut app = App::new();
 
@@ -23,14 +33,24 @@ fn main() {
         .add_plugins(PhysicsPlugin { substeps: 10 })
         .insert_resource(PhysicsParameters {
             debug: true,
-            gravity: 10.0,
+            gravity: Vec3::new(0.0, -10.0, 0.0),
+            linear_damping: 0.0,
+            angular_damping: 0.1,
             stiffness: 1.0,
+            friction: 0.5,
+            contact_prediction: 0.02,
             frequency: 60.0,
             time_scale: 1.0,
+            deterministic: false,
         })
         .add_systems(Startup, setup::camera)
         .add_systems(Update, setup::axes)
-        .add_systems(Startup, init);
+        .add_systems(Startup, init)
+        .add_systems(
+            Update,
+            (move_platforms, walk, drive).before(PhysicsSet::Prepare),
+        )
+        .add_systems(Update, (kick, poke, shoot, report, topple));
 
     app.run();
 }
@@ -40,14 +60,16 @@ fn init(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    commands.spawn((
-        Collider {
-            mass: f32::INFINITY,
-            shape: Shape::Plane { normal: Vec3::Y },
-        },
-        Rigid::default(),
-        TransformBundle::IDENTITY,
-    ));
+    let ground = commands
+        .spawn((
+            Collider {
+                mass: f32::INFINITY,
+                shape: Shape::Plane { normal: Vec3::Y },
+            },
+            RigidBody::Static,
+            TransformBundle::IDENTITY,
+        ))
+        .id();
 
     let length = 1.0;
     let radius = 0.5;
@@ -61,6 +83,7 @@ fn init(
         },
         Linear::default().velocity(Vec3::X),
         Angular::default(),
+        LockedAxes::default().rotation(BVec3::new(true, false, true)),
         PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Capsule {
                 radius,
@@ -78,4 +101,395 @@ fn init(
             ..default()
         },
     ));
+
+    let ball = commands
+        .spawn((
+            Collider {
+                mass: 1.0,
+                shape: Shape::Ball { radius },
+            },
+            Rigid::default(),
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::UVSphere {
+                    radius,
+                    sectors: 64,
+                    stacks: 32,
+                })),
+                material: materials.add(Color::hsl(random::<f32>() * 360.0, 1.0, 0.8).into()),
+                transform: Transform::from_xyz(3.0, 3.0, 0.0),
+                ..default()
+            },
+        ))
+        .id();
+
+    commands.spawn((
+        Collider {
+            mass: f32::INFINITY,
+            shape: Shape::Ball { radius: 1.0 },
+        },
+        Sensor,
+        TransformBundle::from_transform(Transform::from_xyz(6.0, 1.0, 0.0)),
+    ));
+
+    let half_extents = Vec3::new(1.5, 0.25, 1.5);
+    commands.spawn((
+        Collider {
+            mass: f32::INFINITY,
+            shape: Shape::Cuboid { half_extents },
+        },
+        Rigid::default(),
+        RigidBody::Kinematic,
+        Platform,
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box::new(
+                2.0 * half_extents.x,
+                2.0 * half_extents.y,
+                2.0 * half_extents.z,
+            ))),
+            material: materials.add(Color::GRAY.into()),
+            transform: Transform::from_xyz(-4.0, 1.0, 0.0),
+            ..default()
+        },
+    ));
+
+    let size = 1.0;
+    commands.spawn((
+        Collider {
+            mass: 1.0,
+            shape: Shape::Cuboid {
+                half_extents: Vec3::splat(0.5 * size),
+            },
+        },
+        Rigid::default(),
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size })),
+            material: materials.add(Color::hsl(random::<f32>() * 360.0, 1.0, 0.8).into()),
+            transform: Transform::from_xyz(-4.0, 1.75, 0.0),
+            ..default()
+        },
+    ));
+
+    let half_extents = Vec3::new(1.0, 0.1, 1.0);
+    commands.spawn((
+        Collider {
+            mass: f32::INFINITY,
+            shape: Shape::Cuboid { half_extents },
+        },
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box::new(
+                2.0 * half_extents.x,
+                2.0 * half_extents.y,
+                2.0 * half_extents.z,
+            ))),
+            material: materials.add(Color::GRAY.into()),
+            transform: Transform::from_xyz(0.0, half_extents.y, 4.0),
+            ..default()
+        },
+    ));
+
+    let (radius, length) = (0.4, 1.0);
+    commands.spawn((
+        Collider {
+            mass: 1.0,
+            shape: Shape::Capsule { radius, length },
+        },
+        Rigid::default(),
+        RigidBody::Kinematic,
+        CharacterController::default(),
+        CharacterInput::default(),
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Capsule {
+                radius,
+                depth: length,
+                ..default()
+            })),
+            material: materials.add(Color::WHITE.into()),
+            transform: Transform::from_xyz(0.0, radius + 0.5 * length, 6.0),
+            ..default()
+        },
+    ));
+
+    let half_extents = Vec3::new(0.8, 0.25, 1.5);
+    let wheels = [(-0.7, -1.1), (0.7, -1.1), (-0.7, 1.1), (0.7, 1.1)]
+        .into_iter()
+        .map(|(x, z)| {
+            Wheel::new(Vec3::new(x, -0.1, z))
+                .steered(z < 0.0)
+                .driven(z > 0.0)
+        })
+        .collect();
+    commands.spawn((
+        Collider {
+            mass: 4.0,
+            shape: Shape::Cuboid { half_extents },
+        },
+        Rigid::default(),
+        Vehicle::new(wheels),
+        VehicleInput::default(),
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box::new(
+                2.0 * half_extents.x,
+                2.0 * half_extents.y,
+                2.0 * half_extents.z,
+            ))),
+            material: materials.add(Color::hsl(random::<f32>() * 360.0, 1.0, 0.8).into()),
+            transform: Transform::from_xyz(-8.0, 1.0, 0.0),
+            ..default()
+        },
+    ));
+
+    // A skeleton of joint spheres, posed by a ragdoll once toppled.
+    let joint_mesh = meshes.add(Mesh::from(shape::UVSphere {
+        radius: 0.05,
+        ..default()
+    }));
+    let joint_material = materials.add(Color::WHITE.into());
+    let mut joints = Vec::new();
+    let mut joint =
+        |commands: &mut Commands, name: &str, parent: Option<(Entity, Vec3)>, at: Vec3| {
+            let offset = parent.map_or(Vec3::ZERO, |(_, parent_at)| parent_at);
+            let entity = commands
+                .spawn((
+                    Name::new(name.to_string()),
+                    PbrBundle {
+                        mesh: joint_mesh.clone(),
+                        material: joint_material.clone(),
+                        transform: Transform::from_translation(at - offset),
+                        ..default()
+                    },
+                ))
+                .id();
+            if let Some((parent, _)) = parent {
+                commands.entity(parent).add_child(entity);
+            }
+            joints.push(entity);
+            (entity, at)
+        };
+
+    let pelvis = joint(&mut commands, "pelvis", None, Vec3::new(4.0, 1.0, -4.0));
+    let chest = joint(
+        &mut commands,
+        "chest",
+        Some(pelvis),
+        Vec3::new(4.0, 1.4, -4.0),
+    );
+    joint(
+        &mut commands,
+        "head",
+        Some(chest),
+        Vec3::new(4.0, 1.7, -4.0),
+    );
+    for side in [-1.0, 1.0] {
+        let shoulder = joint(
+            &mut commands,
+            "shoulder",
+            Some(chest),
+            Vec3::new(4.0 + 0.2 * side, 1.4, -4.0),
+        );
+        let elbow = joint(
+            &mut commands,
+            "elbow",
+            Some(shoulder),
+            Vec3::new(4.0 + 0.45 * side, 1.4, -4.0),
+        );
+        joint(
+            &mut commands,
+            "hand",
+            Some(elbow),
+            Vec3::new(4.0 + 0.7 * side, 1.4, -4.0),
+        );
+        let hip = joint(
+            &mut commands,
+            "hip",
+            Some(pelvis),
+            Vec3::new(4.0 + 0.1 * side, 1.0, -4.0),
+        );
+        let knee = joint(
+            &mut commands,
+            "knee",
+            Some(hip),
+            Vec3::new(4.0 + 0.1 * side, 0.55, -4.0),
+        );
+        joint(
+            &mut commands,
+            "foot",
+            Some(knee),
+            Vec3::new(4.0 + 0.1 * side, 0.1, -4.0),
+        );
+    }
+    commands.spawn((
+        SkinnedMesh {
+            inverse_bindposes: default(),
+            joints,
+        },
+        RagdollBuilder::default()
+            .mass(10.0)
+            .hinge("elbow", Vec3::Y, (-2.0, 0.0))
+            .hinge("knee", Vec3::X, (0.0, 2.0)),
+    ));
+
+    commands.spawn(
+        SpringJoint::new(ground, ball)
+            .anchors((Vec3::new(3.0, 5.0, 0.0), Vec3::ZERO))
+            .rest_length(2.0)
+            .stiffness(50.0)
+            .damping(0.5),
+    );
+}
+
+fn move_platforms(time: Res<Time>, mut query: Query<&mut Transform, With<Platform>>) {
+    for mut transform in query.iter_mut() {
+        transform.translation.z = 3.0 * (0.5 * time.elapsed_seconds()).sin();
+    }
+}
+
+fn walk(keys: Res<Input<KeyCode>>, mut query: Query<(&CharacterController, &mut CharacterInput)>) {
+    let direction = Vec3::new(
+        f32::from(keys.pressed(KeyCode::Right)) - f32::from(keys.pressed(KeyCode::Left)),
+        0.0,
+        f32::from(keys.pressed(KeyCode::Down)) - f32::from(keys.pressed(KeyCode::Up)),
+    );
+
+    for (controller, mut input) in query.iter_mut() {
+        input.velocity = 3.0 * direction.normalize_or_zero();
+        if keys.just_pressed(KeyCode::J) && controller.is_grounded() {
+            input.jump = true;
+        }
+    }
+}
+
+fn drive(keys: Res<Input<KeyCode>>, mut query: Query<&mut VehicleInput>) {
+    let axis =
+        |positive, negative| f32::from(keys.pressed(positive)) - f32::from(keys.pressed(negative));
+
+    for mut input in query.iter_mut() {
+        input.throttle = axis(KeyCode::W, KeyCode::S);
+        input.steering = axis(KeyCode::D, KeyCode::A);
+        input.brake = f32::from(keys.pressed(KeyCode::B));
+    }
+}
+
+fn topple(keys: Res<Input<KeyCode>>, mut ragdolls: Query<&mut Ragdoll>) {
+    if keys.just_pressed(KeyCode::R) {
+        for mut ragdoll in ragdolls.iter_mut() {
+            ragdoll.mode = match ragdoll.mode {
+                RagdollMode::Animated => RagdollMode::Simulated,
+                RagdollMode::Simulated => RagdollMode::Animated,
+            };
+        }
+    }
+}
+
+fn kick(
+    mut commands: Commands,
+    mut explosions: EventWriter<Explosion>,
+    keys: Res<Input<KeyCode>>,
+    query: Query<(Entity, &Transform), With<Linear>>,
+) {
+    if keys.just_pressed(KeyCode::E) {
+        explosions.send(Explosion {
+            center: Vec3::ZERO,
+            radius: 5.0,
+            strength: 10.0,
+            mask: u32::MAX,
+        });
+    }
+
+    if keys.just_pressed(KeyCode::Space) {
+        for (entity, transform) in query.iter() {
+            let offset = 0.2 * Vec3::new(random::<f32>(), 0.0, random::<f32>());
+            commands
+                .entity(entity)
+                .apply_impulse(5.0 * Vec3::Y, transform.translation + offset);
+        }
+    }
+}
+
+fn poke(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    spatial_query: SpatialQuery,
+    buttons: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+) {
+    let Some(cursor) = windows.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    if let Some(hit) =
+        spatial_query.cast_ray(ray.origin, ray.direction, 100.0, &QueryFilter::default())
+    {
+        gizmos.ray(hit.point, 0.5 * hit.normal, Color::WHITE);
+        if buttons.just_pressed(MouseButton::Left) {
+            commands
+                .entity(hit.entity)
+                .apply_impulse(2.0 * ray.direction, hit.point);
+        }
+    }
+}
+
+/// Fires a small, fast ball from the camera, which would tunnel through thin colliders without CCD.
+fn shoot(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    keys: Res<Input<KeyCode>>,
+    cameras: Query<&Transform, With<Camera>>,
+) {
+    if !keys.just_pressed(KeyCode::F) {
+        return;
+    }
+
+    let radius = 0.1;
+    for camera in cameras.iter() {
+        commands.spawn((
+            Collider {
+                mass: 0.1,
+                shape: Shape::Ball { radius },
+            },
+            Rigid::default(),
+            Linear::default().velocity(100.0 * camera.forward()),
+            Ccd,
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::UVSphere {
+                    radius,
+                    ..default()
+                })),
+                material: materials.add(Color::ORANGE_RED.into()),
+                transform: Transform::from_translation(camera.translation),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn report(
+    collisions: Res<Collisions>,
+    mut started: EventReader<CollisionStarted>,
+    mut ended: EventReader<CollisionEnded>,
+    mut enter: EventReader<TriggerEnter>,
+    mut exit: EventReader<TriggerExit>,
+) {
+    for CollisionStarted(a, b) in started.read() {
+        let impulse = collisions
+            .get(*a, *b)
+            .map_or(0.0, |collision| collision.normal_impulse);
+        info!("{a:?} hit {b:?} with an impulse of {impulse}");
+    }
+    for CollisionEnded(a, b) in ended.read() {
+        info!("{a:?} separated from {b:?}");
+    }
+    for TriggerEnter(sensor, other) in enter.read() {
+        info!("{other:?} entered checkpoint {sensor:?}");
+    }
+    for TriggerExit(sensor, other) in exit.read() {
+        info!("{other:?} left checkpoint {sensor:?}");
+    }
 }
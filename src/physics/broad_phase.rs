@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use parry3d::bounding_volume::{Aabb, BoundingVolume};
+
+use super::{
+    collider::{CollisionLayers, Shape},
+    convert,
+};
+
+/// Distance by which bounds are loosened to catch contacts arising from corrections during a step.
+const MARGIN: f32 = 0.05;
+
+/// Bounds of all colliders over the course of a step, used to quickly discard pairs which cannot touch.
+/// Colliders spawned since the last step are not yet known to the broad phase.
+#[derive(Resource, Debug, Default)]
+pub struct BroadPhase {
+    /// Sorted by their lower bound along the X-axis, then by entity.
+    bounds: Vec<(Entity, Aabb, CollisionLayers)>,
+    pairs: Vec<(Entity, Entity)>,
+}
+
+impl BroadPhase {
+    /// Pairs of interacting colliders whose bounds overlap.
+    pub fn pairs(&self) -> &[(Entity, Entity)] {
+        &self.pairs
+    }
+
+    pub(super) fn bounds(&self) -> impl Iterator<Item = (Entity, &Aabb, CollisionLayers)> {
+        self.bounds
+            .iter()
+            .map(|(entity, aabb, layers)| (*entity, aabb, *layers))
+    }
+
+    /// Replaces all bounds and finds overlapping pairs by sweeping along the X-axis.
+    /// If `sorted`, each pair and the pairs themselves are ordered by entity instead of by the sweep.
+    pub(super) fn update(
+        &mut self,
+        bounds: impl Iterator<Item = (Entity, Aabb, CollisionLayers)>,
+        sorted: bool,
+    ) {
+        self.bounds.clear();
+        self.bounds.extend(bounds);
+        self.bounds.sort_by(|(entity_0, a, _), (entity_1, b, _)| {
+            a.mins.x.total_cmp(&b.mins.x).then(entity_0.cmp(entity_1))
+        });
+
+        self.pairs.clear();
+        for (i, (entity_0, aabb_0, layers_0)) in self.bounds.iter().enumerate() {
+            for (entity_1, aabb_1, layers_1) in &self.bounds[i + 1..] {
+                if aabb_1.mins.x > aabb_0.maxs.x {
+                    break;
+                }
+                if layers_0.interacts(layers_1) && aabb_0.intersects(aabb_1) {
+                    self.pairs.push((*entity_0, *entity_1));
+                }
+            }
+        }
+
+        if sorted {
+            for pair in &mut self.pairs {
+                *pair = (pair.0.min(pair.1), pair.0.max(pair.1));
+            }
+            self.pairs.sort_unstable();
+        }
+    }
+}
+
+/// The bounds of a shape moving by the given offset.
+pub(super) fn swept_aabb(shape: &Shape, transform: &Transform, motion: Vec3) -> Aabb {
+    let aabb = shape
+        .parry_shape()
+        .compute_aabb(&convert::to_iso(*transform));
+    let motion = convert::to_vec(motion);
+    aabb.merged(&Aabb::new(aabb.mins + motion, aabb.maxs + motion))
+        .loosened(MARGIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use parry3d::math::Point;
+
+    use super::*;
+
+    fn unit_box(entity: u32, x: f32) -> (Entity, Aabb, CollisionLayers) {
+        let aabb = Aabb::new(Point::new(x, 0.0, 0.0), Point::new(x + 1.0, 1.0, 1.0));
+        (Entity::from_raw(entity), aabb, CollisionLayers::ALL)
+    }
+
+    #[test]
+    fn only_overlapping_bounds_pair_up() {
+        let mut broad_phase = BroadPhase::default();
+        let bounds = [unit_box(3, 0.0), unit_box(1, 0.5), unit_box(2, 3.0)];
+        broad_phase.update(bounds.into_iter(), false);
+        assert_eq!(
+            broad_phase.pairs(),
+            [(Entity::from_raw(3), Entity::from_raw(1))]
+        );
+
+        broad_phase.update(bounds.into_iter(), true);
+        assert_eq!(
+            broad_phase.pairs(),
+            [(Entity::from_raw(1), Entity::from_raw(3))]
+        );
+    }
+
+    #[test]
+    fn bounds_on_disjoint_layers_never_pair_up() {
+        let mut broad_phase = BroadPhase::default();
+        let (mut debris, mut other) = (unit_box(1, 0.0), unit_box(2, 0.5));
+        debris.2 = CollisionLayers::new(0b01, 0b10);
+        other.2 = CollisionLayers::new(0b01, 0b10);
+        broad_phase.update([debris, other].into_iter(), true);
+        assert!(broad_phase.pairs().is_empty());
+
+        other.2 = CollisionLayers::new(0b10, 0b01);
+        broad_phase.update([debris, other].into_iter(), true);
+        assert_eq!(broad_phase.pairs().len(), 1);
+    }
+}
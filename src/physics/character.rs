@@ -0,0 +1,354 @@
+use bevy::prelude::*;
+use derive_setters::Setters;
+
+use super::{
+    collider::Shape,
+    math,
+    query::{QueryFilter, ShapeHit, SpatialQuery},
+};
+
+/// Moves a kinematic body by sweeping its collider, typically a [`Shape::Capsule`], through the world
+/// instead of simulating it. Obstacles are slid along, small ledges are stepped onto
+/// and dynamic bodies in the way are pushed.
+#[derive(Component, Debug, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct CharacterController {
+    pub up: Vec3,
+    /// The steepest slope in radians the character can stand on and walk up.
+    pub max_slope: f32,
+    /// The highest ledge the character climbs onto without jumping.
+    pub step_height: f32,
+    /// The distance within which a walking character is pulled down onto the ground,
+    /// so it does not lift off when walking down slopes or stairs.
+    pub snap_distance: f32,
+    /// The gap kept between the collider and obstacles, so casts start outside of them.
+    pub skin: f32,
+    pub jump_speed: f32,
+    /// The mass with which the character pushes dynamic bodies.
+    pub mass: f32,
+    /// The maximum number of obstacles slid along per step.
+    pub max_slides: usize,
+    ground: Option<Vec3>,
+    vertical_speed: f32,
+}
+
+/// The movement requested from a [`CharacterController`].
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct CharacterInput {
+    /// The desired velocity along the ground. Any component along the up axis is ignored.
+    pub velocity: Vec3,
+    /// Leaves the ground with the jump speed. Reset once the character has jumped.
+    pub jump: bool,
+}
+
+/// A dynamic body the character ran into, along with the velocity it had at that moment.
+pub(super) struct Push {
+    pub hit: ShapeHit,
+    pub velocity: Vec3,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            up: Vec3::Y,
+            max_slope: 45f32.to_radians(),
+            step_height: 0.3,
+            snap_distance: 0.2,
+            skin: 0.01,
+            jump_speed: 5.0,
+            mass: 1.0,
+            max_slides: 4,
+            ground: None,
+            vertical_speed: 0.0,
+        }
+    }
+}
+
+impl CharacterController {
+    pub fn is_grounded(&self) -> bool {
+        self.ground.is_some()
+    }
+
+    /// The normal of the walkable surface the character stands on.
+    #[allow(unused)]
+    pub fn ground_normal(&self) -> Option<Vec3> {
+        self.ground
+    }
+
+    /// Computes the translation of the character at the end of the step.
+    /// Every obstacle hit while walking is reported, so dynamic bodies among them can be pushed.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn step(
+        &mut self,
+        input: &mut CharacterInput,
+        shape: &Shape,
+        transform: &Transform,
+        query: &SpatialQuery,
+        filter: &QueryFilter,
+        gravity: Vec3,
+        dt: f32,
+    ) -> (Vec3, Vec<Push>) {
+        let sweep = Sweep {
+            controller: self,
+            shape,
+            rotation: transform.rotation,
+            query,
+            filter,
+        };
+
+        let was_grounded = self.ground.is_some();
+        let mut vertical_speed = if was_grounded {
+            0.0
+        } else {
+            self.vertical_speed + dt * gravity.dot(self.up)
+        };
+        if was_grounded && input.jump {
+            vertical_speed = self.jump_speed;
+            input.jump = false;
+        }
+
+        let velocity = input.velocity - input.velocity.dot(self.up) * self.up;
+        let start = transform.translation;
+        let mut walk = Vec::new();
+        let (mut translation, blocked) = sweep.walk(start, dt * velocity, &mut walk);
+
+        if was_grounded && blocked && self.step_height > 0.0 {
+            if let Some(stepped) = sweep.step_up(start, dt * velocity) {
+                let progress = |end: Vec3| (end - start).reject_from_normalized(self.up).length();
+                if progress(stepped) > progress(translation) {
+                    translation = stepped;
+                }
+            }
+        }
+
+        let (mut translation, hit) = sweep.fall(translation, dt * vertical_speed);
+        let mut ground = None;
+        if let Some(hit) = hit {
+            if vertical_speed <= 0.0 {
+                ground = Some(hit.normal);
+            }
+            vertical_speed = 0.0;
+        }
+
+        if ground.is_none() && was_grounded && vertical_speed <= 0.0 {
+            if let Some((snapped, normal)) = sweep.land(translation, self.snap_distance) {
+                translation = snapped;
+                ground = Some(normal);
+                vertical_speed = 0.0;
+            }
+        }
+
+        self.ground = ground;
+        self.vertical_speed = vertical_speed;
+
+        let pushes = walk.into_iter().map(|hit| Push { hit, velocity }).collect();
+        (translation, pushes)
+    }
+
+    fn is_walkable(&self, normal: Vec3) -> bool {
+        normal.dot(self.up) >= math::cos(self.max_slope)
+    }
+}
+
+/// Casts of the character's collider at a fixed rotation.
+struct Sweep<'a, 'w, 's> {
+    controller: &'a CharacterController,
+    shape: &'a Shape,
+    rotation: Quat,
+    query: &'a SpatialQuery<'w, 's>,
+    filter: &'a QueryFilter,
+}
+
+impl Sweep<'_, '_, '_> {
+    /// Moves as far as possible along the motion, stopping short of the first obstacle by the skin width.
+    fn cast(&self, translation: Vec3, motion: Vec3) -> (Vec3, Option<ShapeHit>) {
+        let distance = motion.length();
+        if distance <= f32::EPSILON {
+            return (translation, None);
+        }
+        let direction = motion / distance;
+        let skin = self.controller.skin;
+
+        match self.query.cast_shape(
+            self.shape,
+            &Transform::from_translation(translation).with_rotation(self.rotation),
+            direction,
+            distance + skin,
+            self.filter,
+        ) {
+            Some(hit) => (
+                translation + (hit.distance - skin).clamp(0.0, distance) * direction,
+                Some(hit),
+            ),
+            None => (translation + motion, None),
+        }
+    }
+
+    /// Moves along the ground, sliding along obstacles.
+    /// Steep slopes are treated as walls, so they can not be climbed. Also tells whether such a wall was hit.
+    fn walk(&self, mut translation: Vec3, motion: Vec3, hits: &mut Vec<ShapeHit>) -> (Vec3, bool) {
+        let up = self.controller.up;
+        let mut remaining = motion;
+        let mut blocked = false;
+
+        for _ in 0..self.controller.max_slides {
+            let (moved, hit) = self.cast(translation, remaining);
+            let Some(hit) = hit else {
+                return (moved, blocked);
+            };
+            remaining -= moved - translation;
+            translation = moved;
+
+            let normal = if self.controller.is_walkable(hit.normal) {
+                hit.normal
+            } else {
+                blocked = true;
+                hit.normal.reject_from_normalized(up).normalize_or_zero()
+            };
+            remaining -= remaining.dot(normal).min(0.0) * normal;
+            hits.push(hit);
+        }
+
+        (translation, blocked)
+    }
+
+    /// Walks over a ledge by lifting the character, walking and putting it back down onto walkable ground.
+    fn step_up(&self, translation: Vec3, motion: Vec3) -> Option<Vec3> {
+        let up = self.controller.up;
+        let (lifted, _) = self.cast(translation, self.controller.step_height * up);
+        let (walked, _) = self.walk(lifted, motion, &mut Vec::new());
+        let (landed, _) = self.land(walked, (lifted - translation).dot(up))?;
+        Some(landed)
+    }
+
+    /// Moves along the up axis, sliding down steep slopes.
+    /// Stops at walkable ground when moving down and at anything when moving up, returning what was hit.
+    fn fall(&self, mut translation: Vec3, distance: f32) -> (Vec3, Option<ShapeHit>) {
+        let up = self.controller.up;
+        let mut remaining = distance * up;
+
+        for _ in 0..self.controller.max_slides {
+            let (moved, hit) = self.cast(translation, remaining);
+            let Some(hit) = hit else {
+                return (moved, None);
+            };
+            remaining -= moved - translation;
+            translation = moved;
+
+            if distance > 0.0 || self.controller.is_walkable(hit.normal) {
+                return (translation, Some(hit));
+            }
+            remaining -= remaining.dot(hit.normal).min(0.0) * hit.normal;
+        }
+
+        (translation, None)
+    }
+
+    /// Moves down onto walkable ground within the given distance.
+    fn land(&self, translation: Vec3, distance: f32) -> Option<(Vec3, Vec3)> {
+        let (landed, hit) = self.cast(translation, -distance * self.controller.up);
+        let normal = self.surface_normal(translation, &hit?);
+        self.controller
+            .is_walkable(normal)
+            .then_some((landed, normal))
+    }
+
+    /// The normal of the surface at a hit, which differs from the hit normal when standing on an edge.
+    fn surface_normal(&self, translation: Vec3, hit: &ShapeHit) -> Vec3 {
+        let up = self.controller.up;
+        let skin = self.controller.skin;
+        let outwards = (hit.point - translation)
+            .reject_from_normalized(up)
+            .normalize_or_zero();
+        self.query
+            .cast_ray(
+                hit.point + skin * (up + outwards),
+                -up,
+                2.0 * skin,
+                self.filter,
+            )
+            .filter(|ray| ray.entity == hit.entity)
+            .map_or(hit.normal, |ray| ray.normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::physics::{
+        collider::Collider,
+        motion::RigidBody,
+        tests::{app, ground},
+        PhysicsParameters,
+    };
+
+    const DT: f32 = 0.1;
+
+    /// Walks a ball-shaped character starting just above the ground at the origin for a second,
+    /// among static obstacles given as boxes by their centers and half extents.
+    fn walk(obstacles: &[(Vec3, Vec3)], velocity: Vec3) -> (Vec3, CharacterController) {
+        let mut app = app(PhysicsParameters::default());
+        ground(&mut app);
+        for &(center, half_extents) in obstacles {
+            app.world.spawn((
+                Collider {
+                    mass: f32::INFINITY,
+                    shape: Shape::Cuboid { half_extents },
+                },
+                RigidBody::Static,
+                TransformBundle::from_transform(Transform::from_translation(center)),
+            ));
+        }
+        app.update();
+
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let mut controller = CharacterController::default();
+        let mut input = CharacterInput {
+            velocity,
+            jump: false,
+        };
+        let mut transform = Transform::from_xyz(0.0, 0.5 + controller.skin, 0.0);
+        for _ in 0..10 {
+            let (translation, _) = controller.step(
+                &mut input,
+                &Shape::Ball { radius: 0.5 },
+                &transform,
+                &query,
+                &QueryFilter::default(),
+                -10.0 * Vec3::Y,
+                DT,
+            );
+            transform.translation = translation;
+        }
+        (transform.translation, controller)
+    }
+
+    #[test]
+    fn characters_slide_along_walls() {
+        let wall = (Vec3::new(1.5, 1.0, 0.0), Vec3::new(0.5, 1.0, 10.0));
+        let (translation, controller) = walk(&[wall], Vec3::new(2.0, 0.0, 2.0));
+
+        assert!(controller.is_grounded());
+        assert!((translation.y - 0.51).abs() < 1e-3);
+        // Stopped by the wall face at x = 1, keeping the skin width, but moving along it unhindered.
+        assert!(translation.x < 0.5 && translation.x > 0.48);
+        assert!((translation.z - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn characters_step_onto_low_ledges() {
+        let ledge = (Vec3::new(5.5, 0.1, 0.0), Vec3::new(5.0, 0.1, 5.0));
+        let (translation, controller) = walk(&[ledge], Vec3::new(2.0, 0.0, 0.0));
+        assert!(controller.is_grounded());
+        assert!((translation.y - 0.71).abs() < 1e-3);
+        assert!(translation.x > 1.5);
+
+        let wall = (Vec3::new(5.5, 0.5, 0.0), Vec3::new(5.0, 0.5, 5.0));
+        let (translation, _) = walk(&[wall], Vec3::new(2.0, 0.0, 0.0));
+        assert!((translation.y - 0.51).abs() < 1e-3);
+        assert!(translation.x < 0.5);
+    }
+}
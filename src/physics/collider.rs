@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use parry3d::{na::Unit, simba::scalar::SubsetOf};
+use parry3d::{na::Unit, query::TOIStatus, simba::scalar::SubsetOf};
 
 use super::{convert, util::Point};
 
@@ -20,6 +20,46 @@ pub enum Shape {
     Capsule { radius: f32, length: f32 },
     #[allow(unused)]
     Plane { normal: Vec3 },
+    #[allow(unused)]
+    Cuboid { half_extents: Vec3 },
+}
+
+/// Makes a collider report overlaps through trigger events without ever being pushed apart.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Sensor;
+
+/// Overrides the global contact prediction distance for a collider.
+/// Pairs use the larger distance of both colliders.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ContactPrediction(pub f32);
+
+/// The layers a collider belongs to and the layers it collides with, as bitmasks.
+/// Two colliders only interact if each one is a member of a layer the other one collides with.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionLayers {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl CollisionLayers {
+    pub const ALL: Self = Self::new(u32::MAX, u32::MAX);
+
+    pub const fn new(memberships: u32, filters: u32) -> Self {
+        Self {
+            memberships,
+            filters,
+        }
+    }
+
+    pub fn interacts(&self, other: &Self) -> bool {
+        self.memberships & other.filters != 0 && other.memberships & self.filters != 0
+    }
 }
 
 pub struct Contact {
@@ -28,10 +68,12 @@ pub struct Contact {
     pub depth: f32,
 }
 
+/// Finds the contact between two colliders, if they penetrate or are closer than the prediction distance.
 pub fn contact(
     colliders: (&Collider, &Collider),
     translations: (Vec3, Vec3),
     rotations: (Quat, Quat),
+    prediction: f32,
 ) -> Option<Contact> {
     if let Some(c) = parry3d::query::contact::contact(
         &convert::to_iso(Transform {
@@ -46,7 +88,7 @@ pub fn contact(
             scale: Vec3::ONE,
         }),
         colliders.1.shape.parry_shape().as_ref(),
-        0.0,
+        prediction,
     )
     .unwrap()
     {
@@ -63,24 +105,84 @@ pub fn contact(
     }
 }
 
+pub fn intersects(shapes: (&Shape, &Shape), transforms: (&Transform, &Transform)) -> bool {
+    parry3d::query::intersection_test(
+        &convert::to_iso(*transforms.0),
+        shapes.0.parry_shape().as_ref(),
+        &convert::to_iso(*transforms.1),
+        shapes.1.parry_shape().as_ref(),
+    )
+    .unwrap_or(false)
+}
+
+pub struct Impact {
+    /// The fraction of the motions after which the shapes touch.
+    pub time: f32,
+    /// The outward normals of both shapes at the impact.
+    pub normals: (Vec3, Vec3),
+}
+
+/// Finds the first impact of two shapes moving from their transforms.
+/// Shapes which already overlap at their transforms are not considered to impact.
+pub fn time_of_impact(
+    shapes: (&Shape, &Shape),
+    transforms: (&Transform, &Transform),
+    motions: (Vec3, Vec3),
+) -> Option<Impact> {
+    let toi = parry3d::query::time_of_impact(
+        &convert::to_iso(*transforms.0),
+        &convert::to_vec(motions.0),
+        shapes.0.parry_shape().as_ref(),
+        &convert::to_iso(*transforms.1),
+        &convert::to_vec(motions.1),
+        shapes.1.parry_shape().as_ref(),
+        1.0,
+        false,
+    )
+    .ok()??;
+    (toi.status != TOIStatus::Penetrating).then(|| Impact {
+        time: toi.toi,
+        normals: (
+            transforms.0.rotation * convert::vec(toi.normal1.into_inner()),
+            transforms.1.rotation * convert::vec(toi.normal2.into_inner()),
+        ),
+    })
+}
+
+/// Projects a point onto the surface of a shape, also telling whether the point lies inside.
+pub fn project_point(shape: &Shape, transform: &Transform, point: Vec3) -> (Vec3, bool) {
+    let projection = shape.parry_shape().project_point(
+        &convert::to_iso(*transform),
+        &convert::to_point(point),
+        false,
+    );
+    (convert::point(projection.point), projection.is_inside)
+}
+
 impl Collider {
     pub fn inv_mass(&self) -> f32 {
         self.mass.recip()
     }
 
     pub fn inv_moment_of_inertia(&self) -> Vec3 {
-        let inverse_tensor = self
-            .shape
-            .parry_shape()
-            .mass_properties(1.0) // TODO: Density is hardcoded here
-            .inv_principal_inertia_sqrt
-            .map(|v| v * v);
-        convert::vec(inverse_tensor)
+        // Parry computes the inertia for a unit density, which is rescaled to the mass of the collider.
+        let properties = self.shape.parry_shape().mass_properties(1.0);
+        let inverse_tensor = properties.inv_principal_inertia_sqrt.map(|v| v * v);
+        convert::vec(inverse_tensor) * properties.mass() / self.mass
     }
 }
 
 impl Shape {
-    fn parry_shape(self) -> Box<dyn parry3d::shape::Shape> {
+    /// The shortest distance from the center of the shape to its surface.
+    pub(super) fn inner_radius(&self) -> f32 {
+        match *self {
+            Shape::Ball { radius } | Shape::Capsule { radius, .. } => radius,
+            Shape::Plane { .. } => f32::INFINITY,
+            Shape::Cuboid { half_extents } => half_extents.min_element(),
+        }
+    }
+
+    pub(super) fn parry_shape(self) -> Box<dyn parry3d::shape::Shape> {
         match self {
             Shape::Ball { radius } => Box::new(parry3d::shape::Ball { radius }),
             Shape::Capsule { radius, length } => Box::new(parry3d::shape::Capsule {
@@ -93,6 +195,74 @@ impl Shape {
             Shape::Plane { normal } => Box::new(parry3d::shape::HalfSpace {
                 normal: Unit::new_normalize(convert::to_vec(normal)),
             }),
+            Shape::Cuboid { half_extents } => {
+                Box::new(parry3d::shape::Cuboid::new(convert::to_vec(half_extents)))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ball_inertia_scales_with_its_mass() {
+        let (mass, radius) = (3.0, 0.5);
+        let collider = Collider {
+            mass,
+            shape: Shape::Ball { radius },
+        };
+        let expected = 5.0 / (2.0 * mass * radius * radius);
+        assert!(collider
+            .inv_moment_of_inertia()
+            .abs_diff_eq(Vec3::splat(expected), 1e-4));
+    }
+
+    #[test]
+    fn moving_shapes_impact_once_they_touch() {
+        let ball = Shape::Ball { radius: 0.5 };
+        let impact = |x: f32, motion: f32| {
+            time_of_impact(
+                (&ball, &ball),
+                (&Transform::from_xyz(x, 0.0, 0.0), &Transform::IDENTITY),
+                (motion * Vec3::X, Vec3::ZERO),
+            )
+        };
+
+        let hit = impact(-3.0, 4.0).unwrap();
+        assert!((hit.time - 0.5).abs() < 1e-5);
+        assert!(hit.normals.0.abs_diff_eq(Vec3::X, 1e-5));
+        assert!(hit.normals.1.abs_diff_eq(Vec3::NEG_X, 1e-5));
+
+        assert!(impact(-3.0, 1.5).is_none());
+        assert!(impact(-0.5, 4.0).is_none());
+    }
+
+    #[test]
+    fn contacts_are_predicted_within_the_prediction_distance() {
+        let ball = Collider {
+            mass: 1.0,
+            shape: Shape::Ball { radius: 0.5 },
+        };
+        let contact = |gap: f32| {
+            contact(
+                (&ball, &ball),
+                (Vec3::ZERO, (1.0 + gap) * Vec3::X),
+                (Quat::IDENTITY, Quat::IDENTITY),
+                0.02,
+            )
+        };
+
+        let speculative = contact(0.01).unwrap();
+        assert!((speculative.depth - 0.01).abs() < 1e-5);
+        assert!(speculative.normals.0.abs_diff_eq(Vec3::X, 1e-5));
+        assert!(speculative.points.0.abs_diff_eq(0.5 * Vec3::X, 1e-5));
+        assert!(speculative.points.1.abs_diff_eq(0.51 * Vec3::X, 1e-5));
+
+        let penetrating = contact(-0.1).unwrap();
+        assert!((penetrating.depth + 0.1).abs() < 1e-5);
+
+        assert!(contact(0.05).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+/// Accelerates every dynamic body overlapping the [`Sensor`](super::collider::Sensor) collider of its entity.
+///
+/// Only bodies sharing a [`CollisionLayers`](super::collider::CollisionLayers) layer with the field are affected.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ForceField {
+    pub model: FieldModel,
+    pub mode: FieldMode,
+}
+
+/// How the field's effect is distributed in space.
+/// Positions and directions are relative to the field's transform.
+#[derive(Debug, Clone, Copy)]
+pub enum FieldModel {
+    /// Pushes along a fixed direction whose length is the strength, e.g. wind tunnels and lifts.
+    #[allow(unused)]
+    Directional { direction: Vec3 },
+    /// Attracts towards the field's origin, or repels for negative strengths, e.g. planets.
+    #[allow(unused)]
+    Radial { strength: f32, falloff: Falloff },
+    /// Pushes around the axis through the field's origin, e.g. whirlpools.
+    #[allow(unused)]
+    Vortex {
+        axis: Vec3,
+        strength: f32,
+        falloff: Falloff,
+    },
+}
+
+/// How the strength decreases with distance from the field's origin or axis.
+#[derive(Debug, Clone, Copy)]
+pub enum Falloff {
+    #[allow(unused)]
+    Constant,
+    /// Decreases linearly until vanishing at the given radius.
+    #[allow(unused)]
+    Linear { radius: f32 },
+    #[allow(unused)]
+    InverseSquare,
+}
+
+/// Whether the field acts like gravity or like a force.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum FieldMode {
+    /// Accelerates all bodies alike, regardless of their mass.
+    #[default]
+    Acceleration,
+    /// Accelerates lighter bodies more than heavier ones.
+    #[allow(unused)]
+    Force,
+}
+
+impl Falloff {
+    fn factor(self, distance: f32) -> f32 {
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear { radius } => (1.0 - distance / radius).max(0.0),
+            Falloff::InverseSquare => (distance * distance).max(f32::EPSILON).recip(),
+        }
+    }
+}
+
+impl ForceField {
+    /// Evaluates the field at a world-space point.
+    pub fn evaluate(&self, transform: &Transform, point: Vec3) -> Vec3 {
+        let offset = point - transform.translation;
+        match self.model {
+            FieldModel::Directional { direction } => transform.rotation * direction,
+            FieldModel::Radial { strength, falloff } => {
+                let distance = offset.length();
+                -strength * falloff.factor(distance) * offset.normalize_or_zero()
+            }
+            FieldModel::Vortex {
+                axis,
+                strength,
+                falloff,
+            } => {
+                let axis = (transform.rotation * axis).normalize_or_zero();
+                let radial = offset - offset.dot(axis) * axis;
+                let distance = radial.length();
+                strength * falloff.factor(distance) * axis.cross(radial).normalize_or_zero()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{
+        collider::{Collider, CollisionLayers, Sensor, Shape},
+        motion::Linear,
+        tests::{app, ball},
+        PhysicsParameters,
+    };
+
+    #[test]
+    fn radial_fields_fall_off_towards_their_radius() {
+        let field = ForceField {
+            model: FieldModel::Radial {
+                strength: 4.0,
+                falloff: Falloff::Linear { radius: 2.0 },
+            },
+            mode: FieldMode::Acceleration,
+        };
+        let transform = Transform::from_xyz(1.0, 0.0, 0.0);
+
+        assert_eq!(
+            field.evaluate(&transform, Vec3::new(2.0, 0.0, 0.0)),
+            -2.0 * Vec3::X
+        );
+        assert_eq!(
+            field.evaluate(&transform, Vec3::new(1.0, 3.0, 0.0)),
+            Vec3::ZERO
+        );
+    }
+
+    #[test]
+    fn fields_only_affect_bodies_on_shared_layers() {
+        let mut app = app(PhysicsParameters::default());
+        app.world.spawn((
+            ForceField {
+                model: FieldModel::Directional {
+                    direction: 20.0 * Vec3::Y,
+                },
+                mode: FieldMode::Acceleration,
+            },
+            Collider {
+                mass: f32::INFINITY,
+                shape: Shape::Ball { radius: 10.0 },
+            },
+            Sensor,
+            CollisionLayers::new(0b01, 0b01),
+            TransformBundle::IDENTITY,
+        ));
+        let lifted = ball(&mut app, -2.0 * Vec3::X)
+            .insert(CollisionLayers::new(0b01, 0b01))
+            .id();
+        let ignored = ball(&mut app, 2.0 * Vec3::X)
+            .insert(CollisionLayers::new(0b10, 0b10))
+            .id();
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        assert!(app.world.get::<Linear>(lifted).unwrap().velocity.y > 0.0);
+        assert!(app.world.get::<Linear>(ignored).unwrap().velocity.y < 0.0);
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use super::convert;
+
+/// A body of fluid which lifts and slows down every dynamic body submerged in it.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FluidVolume {
+    pub region: FluidRegion,
+    pub density: f32,
+    /// Drag force per unit of velocity of a fully submerged body.
+    pub linear_drag: f32,
+    /// Drag torque per unit of angular velocity of a fully submerged body.
+    pub angular_drag: f32,
+}
+
+/// The extent of a fluid relative to its transform.
+/// The fluid's surface is always perpendicular to the local Y-axis.
+#[derive(Debug, Clone, Copy)]
+pub enum FluidRegion {
+    /// Everything below the surface passing through the origin.
+    #[allow(unused)]
+    HalfSpace,
+    /// A box centered at the origin with the surface at its top face.
+    #[allow(unused)]
+    Cuboid { half_extents: Vec3 },
+}
+
+/// The part of a body lying within a fluid.
+pub struct Submersion {
+    pub volume: f32,
+    /// The submerged volume relative to the whole volume of the body.
+    pub fraction: f32,
+    /// The world-space centroid of the submerged volume, where buoyancy attacks.
+    pub center_of_buoyancy: Vec3,
+}
+
+/// Samples per axis used to estimate how much of a shape is submerged.
+const SAMPLES: usize = 6;
+
+impl FluidRegion {
+    fn contains(self, point: Vec3) -> bool {
+        match self {
+            FluidRegion::HalfSpace => point.y <= 0.0,
+            FluidRegion::Cuboid { half_extents } => point.abs().cmple(half_extents).all(),
+        }
+    }
+}
+
+impl FluidVolume {
+    /// Estimates the submerged part of a shape by sampling its bounding box.
+    /// Unbounded shapes such as planes are never considered submerged.
+    pub(super) fn submersion(
+        &self,
+        transform: &Transform,
+        parry_shape: &dyn parry3d::shape::Shape,
+        body_transform: &Transform,
+    ) -> Option<Submersion> {
+        if parry_shape.as_halfspace().is_some() {
+            return None;
+        }
+
+        let aabb = parry_shape.compute_local_aabb();
+        let (mins, extents) = (convert::point(aabb.mins), convert::vec(aabb.extents()));
+        let to_fluid = transform.compute_affine().inverse();
+
+        let mut inside = 0;
+        let mut submerged = 0;
+        let mut centroid = Vec3::ZERO;
+        for i in 0..SAMPLES {
+            for j in 0..SAMPLES {
+                for k in 0..SAMPLES {
+                    let cell = Vec3::new(i as f32, j as f32, k as f32) + 0.5;
+                    let local = mins + extents * cell / SAMPLES as f32;
+                    if !parry_shape.contains_local_point(&convert::to_point(local)) {
+                        continue;
+                    }
+                    inside += 1;
+
+                    let world = body_transform.transform_point(local);
+                    if self.region.contains(to_fluid.transform_point3(world)) {
+                        submerged += 1;
+                        centroid += world;
+                    }
+                }
+            }
+        }
+
+        if submerged == 0 {
+            return None;
+        }
+        let fraction = submerged as f32 / inside as f32;
+        Some(Submersion {
+            volume: fraction * parry_shape.mass_properties(1.0).mass(),
+            fraction,
+            center_of_buoyancy: centroid / submerged as f32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::collider::Shape;
+
+    const WATER: FluidVolume = FluidVolume {
+        region: FluidRegion::HalfSpace,
+        density: 1.0,
+        linear_drag: 0.0,
+        angular_drag: 0.0,
+    };
+
+    #[test]
+    fn bodies_at_the_surface_are_half_submerged() {
+        let shape = Shape::Cuboid {
+            half_extents: Vec3::splat(0.5),
+        };
+        let submersion = WATER
+            .submersion(
+                &Transform::IDENTITY,
+                &*shape.parry_shape(),
+                &Transform::from_xyz(1.0, 0.0, 0.0),
+            )
+            .unwrap();
+
+        assert_eq!(submersion.fraction, 0.5);
+        assert!((submersion.volume - 0.5).abs() < 1e-5);
+        assert!(submersion.center_of_buoyancy.y < 0.0);
+        assert!((submersion.center_of_buoyancy.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bodies_outside_and_planes_are_not_submerged() {
+        let ball = Shape::Ball { radius: 0.5 }.parry_shape();
+        let above = Transform::from_xyz(0.0, 1.0, 0.0);
+        assert!(WATER
+            .submersion(&Transform::IDENTITY, &*ball, &above)
+            .is_none());
+
+        let pool = FluidVolume {
+            region: FluidRegion::Cuboid {
+                half_extents: Vec3::ONE,
+            },
+            ..WATER
+        };
+        let beside = Transform::from_xyz(3.0, 0.0, 0.0);
+        assert!(pool
+            .submersion(&Transform::IDENTITY, &*ball, &beside)
+            .is_none());
+
+        let plane = Shape::Plane { normal: Vec3::Y }.parry_shape();
+        assert!(WATER
+            .submersion(&Transform::IDENTITY, &*plane, &Transform::IDENTITY)
+            .is_none());
+    }
+}
@@ -0,0 +1,286 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use derive_setters::Setters;
+
+/// A force acting on the center of mass of a body with [`Linear`](super::motion::Linear) motion.
+///
+/// Unless persistent, the force is cleared after each step.
+#[derive(Component, Debug, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct ExternalForce {
+    pub force: Vec3,
+    pub persistent: bool,
+}
+
+/// A torque acting on a body with [`Angular`](super::motion::Angular) motion.
+///
+/// Unless persistent, the torque is cleared after each step.
+#[derive(Component, Debug, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct ExternalTorque {
+    pub torque: Vec3,
+    pub persistent: bool,
+}
+
+/// An instantaneous change of momentum, applied at the start of the next step and cleared afterwards.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ExternalImpulse {
+    pub impulse: Vec3,
+    pub angular_impulse: Vec3,
+}
+
+/// Scales the global gravity for a single body, e.g. negative for balloons.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GravityScale(pub f32);
+
+/// Pushes every dynamic body within the radius away from the center.
+///
+/// The impulse attacks at the closest surface point of each body and falls off linearly with distance.
+/// Sensors are never pushed.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Explosion {
+    pub center: Vec3,
+    pub radius: f32,
+    pub strength: f32,
+    /// Only bodies which are members of at least one of these layers are pushed.
+    pub mask: u32,
+}
+
+impl Default for ExternalForce {
+    fn default() -> Self {
+        Self {
+            force: Vec3::ZERO,
+            persistent: true,
+        }
+    }
+}
+
+impl Default for ExternalTorque {
+    fn default() -> Self {
+        Self {
+            torque: Vec3::ZERO,
+            persistent: true,
+        }
+    }
+}
+
+impl Default for GravityScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl ExternalForce {
+    pub fn apply(&mut self, force: Vec3) -> &mut Self {
+        self.force += force;
+        self
+    }
+
+    /// Applies a force at a world-space point, inducing a torque around the center of mass.
+    pub fn apply_at_point(
+        &mut self,
+        torque: &mut ExternalTorque,
+        force: Vec3,
+        point: Vec3,
+        center_of_mass: Vec3,
+    ) -> &mut Self {
+        torque.apply((point - center_of_mass).cross(force));
+        self.apply(force)
+    }
+}
+
+impl ExternalTorque {
+    pub fn apply(&mut self, torque: Vec3) -> &mut Self {
+        self.torque += torque;
+        self
+    }
+}
+
+impl ExternalImpulse {
+    pub fn apply(&mut self, impulse: Vec3) -> &mut Self {
+        self.impulse += impulse;
+        self
+    }
+
+    /// Applies an impulse at a world-space point, inducing an angular impulse around the center of mass.
+    pub fn apply_at_point(
+        &mut self,
+        impulse: Vec3,
+        point: Vec3,
+        center_of_mass: Vec3,
+    ) -> &mut Self {
+        self.angular_impulse += (point - center_of_mass).cross(impulse);
+        self.apply(impulse)
+    }
+}
+
+pub trait ApplyImpulseExt {
+    /// Applies an impulse at a world-space point at the start of the next step.
+    fn apply_impulse(&mut self, impulse: Vec3, point: Vec3) -> &mut Self;
+}
+
+impl ApplyImpulseExt for EntityCommands<'_, '_, '_> {
+    fn apply_impulse(&mut self, impulse: Vec3, point: Vec3) -> &mut Self {
+        // Unlike entity commands, this does not panic if the body was despawned by the time it runs.
+        let entity = self.id();
+        self.commands().add(move |world: &mut World| {
+            let Some(mut body) = world.get_entity_mut(entity) else {
+                return;
+            };
+            let center_of_mass = body
+                .get::<Transform>()
+                .map_or(point, |transform| transform.translation);
+            match body.get_mut::<ExternalImpulse>() {
+                Some(mut external_impulse) => {
+                    external_impulse.apply_at_point(impulse, point, center_of_mass);
+                }
+                None => {
+                    let mut external_impulse = ExternalImpulse::default();
+                    external_impulse.apply_at_point(impulse, point, center_of_mass);
+                    body.insert(external_impulse);
+                }
+            }
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+    use crate::physics::{
+        collider::{CollisionLayers, Sensor},
+        motion::Linear,
+        tests::{app, ball},
+        PhysicsParameters,
+    };
+
+    #[test]
+    fn impulses_accumulate_around_the_center_of_mass() {
+        let mut world = World::new();
+        let body = world.spawn(Transform::from_xyz(1.0, 0.0, 0.0)).id();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        commands
+            .entity(body)
+            .apply_impulse(Vec3::Y, Vec3::new(2.0, 0.0, 0.0))
+            .apply_impulse(Vec3::Y, Vec3::new(1.0, 0.0, 0.0));
+        queue.apply(&mut world);
+
+        let external_impulse = world.get::<ExternalImpulse>(body).unwrap();
+        assert_eq!(external_impulse.impulse, 2.0 * Vec3::Y);
+        assert_eq!(external_impulse.angular_impulse, Vec3::Z);
+    }
+
+    #[test]
+    fn impulses_on_despawned_bodies_are_ignored() {
+        let mut world = World::new();
+        let body = world.spawn(Transform::IDENTITY).id();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        commands.entity(body).despawn();
+        commands.entity(body).apply_impulse(Vec3::Y, Vec3::ZERO);
+        queue.apply(&mut world);
+
+        assert!(world.get_entity(body).is_none());
+    }
+
+    #[test]
+    fn only_persistent_forces_outlast_a_step() {
+        let mut app = app(PhysicsParameters {
+            gravity: Vec3::ZERO,
+            ..default()
+        });
+        let persistent = ball(&mut app, -2.0 * Vec3::X)
+            .insert(ExternalForce::default().force(Vec3::X))
+            .id();
+        let transient = ball(&mut app, 2.0 * Vec3::X)
+            .insert(ExternalForce::default().force(Vec3::X).persistent(false))
+            .id();
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let force = |entity| app.world.get::<ExternalForce>(entity).unwrap().force;
+        assert_eq!(force(persistent), Vec3::X);
+        assert_eq!(force(transient), Vec3::ZERO);
+        let velocity = |entity| app.world.get::<Linear>(entity).unwrap().velocity.x;
+        assert!(velocity(persistent) > velocity(transient));
+        assert!(velocity(transient) > 0.0);
+    }
+
+    #[test]
+    fn gravity_scale_weakens_or_inverts_gravity() {
+        let mut app = app(PhysicsParameters::default());
+        let falling = ball(&mut app, -2.0 * Vec3::X).id();
+        let floating = ball(&mut app, Vec3::ZERO).insert(GravityScale(0.0)).id();
+        let rising = ball(&mut app, 2.0 * Vec3::X)
+            .insert(GravityScale(-0.5))
+            .id();
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let velocity = |entity| app.world.get::<Linear>(entity).unwrap().velocity.y;
+        assert!(velocity(falling) < 0.0);
+        assert_eq!(velocity(floating), 0.0);
+        assert!((velocity(rising) + 0.5 * velocity(falling)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn explosions_push_bodies_away_from_their_center() {
+        let mut app = app(PhysicsParameters {
+            gravity: Vec3::ZERO,
+            ..default()
+        });
+        let near = ball(&mut app, Vec3::X).id();
+        let far = ball(&mut app, -3.0 * Vec3::X).id();
+        let outside = ball(&mut app, 6.0 * Vec3::Y).id();
+        app.update();
+
+        app.world.send_event(Explosion {
+            center: Vec3::ZERO,
+            radius: 5.0,
+            strength: 1.0,
+            mask: u32::MAX,
+        });
+        app.update();
+
+        let velocity = |entity| app.world.get::<Linear>(entity).unwrap().velocity;
+        assert!(velocity(near).x > -velocity(far).x);
+        assert!(velocity(far).x < 0.0);
+        assert_eq!(velocity(outside), Vec3::ZERO);
+    }
+
+    #[test]
+    fn explosions_push_bodies_on_masked_layers_only() {
+        let mut app = app(PhysicsParameters {
+            gravity: Vec3::ZERO,
+            ..default()
+        });
+        let pushed = ball(&mut app, Vec3::X)
+            .insert(CollisionLayers::new(0b01, u32::MAX))
+            .id();
+        let unmasked = ball(&mut app, -Vec3::X)
+            .insert(CollisionLayers::new(0b10, u32::MAX))
+            .id();
+        let sensor = ball(&mut app, 3.0 * Vec3::X)
+            .insert((CollisionLayers::new(0b01, u32::MAX), Sensor))
+            .id();
+        app.update();
+
+        app.world.send_event(Explosion {
+            center: Vec3::ZERO,
+            radius: 5.0,
+            strength: 1.0,
+            mask: 0b01,
+        });
+        app.update();
+
+        let velocity = |entity| app.world.get::<Linear>(entity).unwrap().velocity;
+        assert!(velocity(pushed).x > 0.0);
+        assert_eq!(velocity(unmasked), Vec3::ZERO);
+        assert_eq!(velocity(sensor), Vec3::ZERO);
+    }
+}
@@ -0,0 +1,404 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use derive_setters::Setters;
+
+use super::math;
+
+/// A damped spring connecting two bodies.
+///
+/// Solved as a compliant distance constraint: the stiffness maps onto the XPBD compliance
+/// and the damping onto the XPBD damping coefficient, so an infinitely stiff spring is a rigid rod.
+#[derive(Component, Debug, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct SpringJoint {
+    pub bodies: (Entity, Entity),
+    /// Attachment points in the local space of each body.
+    pub anchors: (Vec3, Vec3),
+    pub rest_length: f32,
+    /// Zero turns the spring into a pure damper.
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl SpringJoint {
+    pub fn new(body_0: Entity, body_1: Entity) -> Self {
+        Self {
+            bodies: (body_0, body_1),
+            anchors: (Vec3::ZERO, Vec3::ZERO),
+            rest_length: 0.0,
+            stiffness: f32::INFINITY,
+            damping: 0.0,
+        }
+    }
+
+    pub fn compliance(&self) -> f32 {
+        self.stiffness.recip()
+    }
+}
+
+/// A ball-and-socket joint holding the anchors of two bodies together.
+///
+/// The swing limit keeps the axis of the second body within a cone around the axis of the first body,
+/// the twist limit bounds the rotation around the axes. Both are measured from the pose
+/// in which the axes and normals of both bodies coincide.
+#[derive(Component, Debug, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct SphericalJoint {
+    pub bodies: (Entity, Entity),
+    /// Attachment points in the local space of each body.
+    pub anchors: (Vec3, Vec3),
+    /// Twist axes in the local space of each body.
+    pub axes: (Vec3, Vec3),
+    /// Directions perpendicular to the axes in the local space of each body, marking zero twist.
+    pub normals: (Vec3, Vec3),
+    /// The largest angle between the axes, in radians.
+    pub swing_limit: f32,
+    /// The largest twist in either direction, in radians.
+    pub twist_limit: f32,
+}
+
+/// A joint holding the anchors of two bodies together while only allowing rotation around a shared axis.
+#[derive(Component, Debug, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct HingeJoint {
+    pub bodies: (Entity, Entity),
+    /// Attachment points in the local space of each body.
+    pub anchors: (Vec3, Vec3),
+    /// Hinge axes in the local space of each body.
+    pub axes: (Vec3, Vec3),
+    /// Directions perpendicular to the axes in the local space of each body, coinciding at a hinge angle of zero.
+    pub normals: (Vec3, Vec3),
+    /// The range of the angle from the first normal to the second one around the axis, in radians.
+    pub limits: (f32, f32),
+}
+
+impl SphericalJoint {
+    pub fn new(body_0: Entity, body_1: Entity) -> Self {
+        Self {
+            bodies: (body_0, body_1),
+            anchors: (Vec3::ZERO, Vec3::ZERO),
+            axes: (Vec3::Y, Vec3::Y),
+            normals: (Vec3::X, Vec3::X),
+            swing_limit: PI,
+            twist_limit: PI,
+        }
+    }
+}
+
+impl HingeJoint {
+    pub fn new(body_0: Entity, body_1: Entity) -> Self {
+        Self {
+            bodies: (body_0, body_1),
+            anchors: (Vec3::ZERO, Vec3::ZERO),
+            axes: (Vec3::X, Vec3::X),
+            normals: (Vec3::Y, Vec3::Y),
+            limits: (-PI, PI),
+        }
+    }
+}
+
+/// The state of one side of a joint during a substep.
+pub(super) struct Attachment {
+    /// Predicted world-space anchor.
+    pub point: Vec3,
+    /// World-space anchor at the start of the substep.
+    pub past_point: Vec3,
+    /// Predicted center of mass.
+    pub center_of_mass: Vec3,
+    /// The inverse mass along each world axis.
+    pub inv_mass: Vec3,
+    /// The world-space inverse inertia tensor.
+    pub inv_moment_of_inertia: Mat3,
+}
+
+impl Attachment {
+    /// The inverse mass of this side when pushed along `direction` at the anchor.
+    pub fn generalized_inv_mass(&self, direction: Vec3) -> f32 {
+        let arm = (self.point - self.center_of_mass).cross(direction);
+        direction.dot(self.inv_mass * direction) + arm.dot(self.inv_moment_of_inertia * arm)
+    }
+
+    /// The inverse moment of inertia of this side when rotated around `axis`.
+    pub fn angular_inv_mass(&self, axis: Vec3) -> f32 {
+        axis.dot(self.inv_moment_of_inertia * axis)
+    }
+}
+
+/// The impulses solving a joint during a substep, pushed onto the first body.
+/// The second body receives the negations.
+#[derive(Debug, Default)]
+pub(super) struct JointImpulses {
+    /// A positional impulse acting at the anchors.
+    pub impulse: Option<Vec3>,
+    pub angular_impulses: Vec<Vec3>,
+}
+
+/// Computes the positional impulse to push onto the first body, the second body receives the negation.
+pub(super) fn spring_impulse(
+    joint: &SpringJoint,
+    attachments: (&Attachment, &Attachment),
+    dt: f32,
+) -> Option<Vec3> {
+    let delta = attachments.0.point - attachments.1.point;
+    let length = delta.length();
+    if length <= f32::EPSILON {
+        return None;
+    }
+    let direction = delta / length;
+    let error = length - joint.rest_length;
+
+    let w = attachments.0.generalized_inv_mass(direction)
+        + attachments.1.generalized_inv_mass(direction);
+    let approach = direction.dot(
+        (attachments.0.point - attachments.0.past_point)
+            - (attachments.1.point - attachments.1.past_point),
+    );
+
+    // Without stiffness the compliance is infinite, which leaves a pure damper in the limit.
+    if joint.stiffness <= 0.0 {
+        let damping = joint.damping.max(0.0) * dt;
+        let lambda = -damping * approach / (damping * w + 1.0);
+        return (lambda != 0.0).then_some(lambda * direction);
+    }
+
+    let compliance = joint.compliance() / (dt * dt);
+    let damping = joint.compliance() * joint.damping.max(0.0) / dt;
+    if w + compliance <= 0.0 {
+        return None;
+    }
+
+    let lambda = (-error - damping * approach) / ((1.0 + damping) * w + compliance);
+
+    Some(lambda * direction)
+}
+
+pub(super) fn spherical_impulses(
+    joint: &SphericalJoint,
+    attachments: (&Attachment, &Attachment),
+    rotations: (Quat, Quat),
+) -> JointImpulses {
+    let axes = (
+        (rotations.0 * joint.axes.0).normalize(),
+        (rotations.1 * joint.axes.1).normalize(),
+    );
+    let mut angular_impulses = Vec::new();
+
+    let swing = axes.0.cross(axes.1);
+    let swing_angle = math::atan2(swing.length(), axes.0.dot(axes.1));
+    if let Some(swing_axis) = swing.try_normalize() {
+        if swing_angle > joint.swing_limit {
+            angular_impulses.extend(rotation_impulse(
+                attachments,
+                (joint.swing_limit - swing_angle) * swing_axis,
+            ));
+        }
+    }
+
+    // Twist is measured around the bisector of the axes, which is undefined when they point apart.
+    if let Some(twist_axis) = (axes.0 + axes.1).try_normalize() {
+        let twist = angle_around(
+            twist_axis,
+            rotations.0 * joint.normals.0,
+            rotations.1 * joint.normals.1,
+        );
+        let limited = twist.clamp(-joint.twist_limit, joint.twist_limit);
+        angular_impulses.extend(rotation_impulse(
+            attachments,
+            (limited - twist) * twist_axis,
+        ));
+    }
+
+    JointImpulses {
+        impulse: anchor_impulse(attachments),
+        angular_impulses,
+    }
+}
+
+pub(super) fn hinge_impulses(
+    joint: &HingeJoint,
+    attachments: (&Attachment, &Attachment),
+    rotations: (Quat, Quat),
+) -> JointImpulses {
+    let axes = (
+        (rotations.0 * joint.axes.0).normalize(),
+        (rotations.1 * joint.axes.1).normalize(),
+    );
+    let mut angular_impulses = Vec::new();
+
+    // Turns the second axis onto the first one.
+    let misalignment = axes.1.cross(axes.0);
+    if let Some(axis) = misalignment.try_normalize() {
+        let angle = math::atan2(misalignment.length(), axes.0.dot(axes.1));
+        angular_impulses.extend(rotation_impulse(attachments, angle * axis));
+    }
+
+    let angle = angle_around(
+        axes.0,
+        rotations.0 * joint.normals.0,
+        rotations.1 * joint.normals.1,
+    );
+    let limited = angle.clamp(joint.limits.0, joint.limits.1);
+    angular_impulses.extend(rotation_impulse(attachments, (limited - angle) * axes.0));
+
+    JointImpulses {
+        impulse: anchor_impulse(attachments),
+        angular_impulses,
+    }
+}
+
+/// Computes the positional impulse pulling the anchors together, like a rigid rod of zero length.
+fn anchor_impulse(attachments: (&Attachment, &Attachment)) -> Option<Vec3> {
+    let delta = attachments.0.point - attachments.1.point;
+    let length = delta.length();
+    if length <= f32::EPSILON {
+        return None;
+    }
+    let direction = delta / length;
+
+    let w = attachments.0.generalized_inv_mass(direction)
+        + attachments.1.generalized_inv_mass(direction);
+    (w > 0.0).then(|| -length / w * direction)
+}
+
+/// Computes the angular impulse that turns the second body relative to the first one by a rotation vector.
+fn rotation_impulse(attachments: (&Attachment, &Attachment), rotation: Vec3) -> Option<Vec3> {
+    let angle = rotation.length();
+    if angle <= f32::EPSILON {
+        return None;
+    }
+    let axis = rotation / angle;
+
+    let w = attachments.0.angular_inv_mass(axis) + attachments.1.angular_inv_mass(axis);
+    (w > 0.0).then(|| -angle / w * axis)
+}
+
+/// The signed angle from `from` to `to` around `axis`, after projecting both onto the plane perpendicular to it.
+pub(super) fn angle_around(axis: Vec3, from: Vec3, to: Vec3) -> f32 {
+    let from = from.reject_from_normalized(axis);
+    let to = to.reject_from_normalized(axis);
+    math::atan2(axis.dot(from.cross(to)), from.dot(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    /// A unit mass at a world-space point, which has not moved during the substep.
+    fn unit_mass(point: Vec3) -> Attachment {
+        Attachment {
+            point,
+            past_point: point,
+            center_of_mass: point,
+            inv_mass: Vec3::ONE,
+            inv_moment_of_inertia: Mat3::IDENTITY,
+        }
+    }
+
+    #[test]
+    fn rigid_springs_restore_their_rest_length_at_once() {
+        let spring = SpringJoint::new(Entity::PLACEHOLDER, Entity::PLACEHOLDER).rest_length(1.0);
+        let attachments = (unit_mass(2.0 * Vec3::X), unit_mass(Vec3::ZERO));
+
+        let impulse = spring_impulse(&spring, (&attachments.0, &attachments.1), 0.1);
+        assert_eq!(impulse, Some(-0.5 * Vec3::X));
+    }
+
+    #[test]
+    fn soft_springs_pull_less_and_damping_resists_approach() {
+        let spring = SpringJoint::new(Entity::PLACEHOLDER, Entity::PLACEHOLDER)
+            .rest_length(1.0)
+            .stiffness(100.0);
+        let mut attachments = (unit_mass(2.0 * Vec3::X), unit_mass(Vec3::ZERO));
+        let soft = spring_impulse(&spring, (&attachments.0, &attachments.1), 0.1).unwrap();
+        assert!(soft.x < 0.0 && soft.x > -0.5);
+
+        // The bodies already approach each other, which the damping counteracts.
+        attachments.0.past_point = 2.1 * Vec3::X;
+        let damped =
+            spring_impulse(&spring.damping(1.0), (&attachments.0, &attachments.1), 0.1).unwrap();
+        assert!(damped.x > soft.x);
+    }
+
+    #[test]
+    fn springs_without_stiffness_only_damp() {
+        let mut attachments = (unit_mass(2.0 * Vec3::X), unit_mass(Vec3::ZERO));
+        for stiffness in [0.0, -10.0] {
+            let spring = SpringJoint::new(Entity::PLACEHOLDER, Entity::PLACEHOLDER)
+                .rest_length(1.0)
+                .stiffness(stiffness);
+            attachments.0.past_point = 2.0 * Vec3::X;
+            assert!(spring_impulse(&spring, (&attachments.0, &attachments.1), 0.1).is_none());
+
+            // Only the approach is resisted, not the stretch beyond the rest length.
+            attachments.0.past_point = 2.1 * Vec3::X;
+            let damped =
+                spring_impulse(&spring.damping(1.0), (&attachments.0, &attachments.1), 0.1)
+                    .unwrap();
+            assert!(damped.is_finite());
+            assert!(damped.x > 0.0 && damped.x < 0.1);
+            assert!(spring_impulse(&spring, (&attachments.0, &attachments.1), 0.1).is_none());
+        }
+    }
+
+    #[test]
+    fn angles_around_an_axis_ignore_axial_components() {
+        assert!((angle_around(Vec3::Z, Vec3::X, Vec3::Y) - FRAC_PI_2).abs() < 1e-6);
+        assert!((angle_around(Vec3::Z, Vec3::X, Vec3::NEG_Y) + FRAC_PI_2).abs() < 1e-6);
+        let angle = angle_around(Vec3::Z, Vec3::new(1.0, 0.0, 5.0), Vec3::new(0.0, 1.0, -3.0));
+        assert!((angle - FRAC_PI_2).abs() < 1e-6);
+    }
+
+    /// Solves a joint between two unit masses with coinciding anchors, the second one rotated.
+    fn angular_impulses(solve: impl Fn((&Attachment, &Attachment)) -> JointImpulses) -> Vec<Vec3> {
+        let attachments = (unit_mass(Vec3::ZERO), unit_mass(Vec3::ZERO));
+        let impulses = solve((&attachments.0, &attachments.1));
+        assert!(impulses.impulse.is_none());
+        impulses.angular_impulses
+    }
+
+    #[test]
+    fn hinges_push_back_beyond_their_limits() {
+        let hinge = HingeJoint::new(Entity::PLACEHOLDER, Entity::PLACEHOLDER).limits((-0.5, 0.5));
+        let solve = |angle: f32| {
+            angular_impulses(|attachments| {
+                hinge_impulses(
+                    &hinge,
+                    attachments,
+                    (Quat::IDENTITY, Quat::from_rotation_x(angle)),
+                )
+            })
+        };
+
+        assert!(solve(0.3).is_empty());
+        // Half of the excess angle is taken back by each of the equally heavy bodies.
+        let [impulse] = solve(1.0)[..] else {
+            panic!("expected a single limit impulse");
+        };
+        assert!(impulse.abs_diff_eq(0.25 * Vec3::X, 1e-5));
+    }
+
+    #[test]
+    fn spherical_joints_limit_swing_and_twist_separately() {
+        let joint = SphericalJoint::new(Entity::PLACEHOLDER, Entity::PLACEHOLDER)
+            .swing_limit(0.5)
+            .twist_limit(0.5);
+        let solve = |rotation: Quat| {
+            angular_impulses(|attachments| {
+                spherical_impulses(&joint, attachments, (Quat::IDENTITY, rotation))
+            })
+        };
+
+        assert!(solve(Quat::from_rotation_z(0.3) * Quat::from_rotation_y(0.3)).is_empty());
+        let [swing] = solve(Quat::from_rotation_z(1.0))[..] else {
+            panic!("expected a single swing impulse");
+        };
+        assert!(swing.abs_diff_eq(0.25 * Vec3::Z, 1e-5));
+        let [twist] = solve(Quat::from_rotation_y(1.0))[..] else {
+            panic!("expected a single twist impulse");
+        };
+        assert!(twist.abs_diff_eq(0.25 * Vec3::Y, 1e-5));
+    }
+}
@@ -0,0 +1,45 @@
+//! Transcendental functions used by the physics, which must not call the platform's math library directly.
+//!
+//! With the `libm` feature they are computed by the portable libm, so results do not depend on the platform.
+//! The feature also switches glam methods such as `length`, `normalize` and `slerp`, and the math inside parry,
+//! over to libm. Square roots and arithmetic are exactly rounded on every platform and need no routing.
+
+#[cfg(feature = "libm")]
+pub fn exp(x: f32) -> f32 {
+    libm::expf(x)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn exp(x: f32) -> f32 {
+    x.exp()
+}
+
+#[cfg(feature = "libm")]
+pub fn cos(x: f32) -> f32 {
+    libm::cosf(x)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn cos(x: f32) -> f32 {
+    x.cos()
+}
+
+#[cfg(feature = "libm")]
+pub fn sin(x: f32) -> f32 {
+    libm::sinf(x)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn sin(x: f32) -> f32 {
+    x.sin()
+}
+
+#[cfg(feature = "libm")]
+pub fn atan2(y: f32, x: f32) -> f32 {
+    libm::atan2f(y, x)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn atan2(y: f32, x: f32) -> f32 {
+    y.atan2(x)
+}
@@ -1,15 +1,55 @@
+pub mod broad_phase;
+pub mod character;
 pub mod collider;
+pub mod field;
+pub mod fluid;
+pub mod force;
+pub mod joint;
 pub mod motion;
+pub mod pairs;
+pub mod query;
+pub mod ragdoll;
+pub mod vehicle;
 
 mod constraint;
 mod convert;
+mod math;
 mod util;
 
-use bevy::prelude::*;
+use std::{
+    collections::BTreeMap,
+    f32::consts::{FRAC_PI_2, PI},
+};
+
+use bevy::{
+    ecs::schedule::ScheduleLabel, prelude::*, render::mesh::skinning::SkinnedMesh, utils::HashMap,
+};
+use parry3d::bounding_volume::BoundingVolume;
 
 use self::{
-    collider::{contact, Collider, Contact},
-    motion::{Angular, Linear},
+    broad_phase::{swept_aabb, BroadPhase},
+    character::{CharacterController, CharacterInput},
+    collider::{
+        contact, intersects, project_point, time_of_impact, Collider, CollisionLayers,
+        ContactPrediction, Sensor, Shape,
+    },
+    field::{FieldMode, ForceField},
+    fluid::FluidVolume,
+    force::{
+        ApplyImpulseExt, Explosion, ExternalForce, ExternalImpulse, ExternalTorque, GravityScale,
+    },
+    joint::{
+        angle_around, hinge_impulses, spherical_impulses, spring_impulse, Attachment, HingeJoint,
+        JointImpulses, SphericalJoint, SpringJoint,
+    },
+    motion::{Angular, AngularDamping, Ccd, Linear, LinearDamping, LockedAxes, RigidBody},
+    pairs::{
+        CollisionEnded, CollisionStarted, Collisions, ContactPair, Contacts, TriggerEnter,
+        TriggerExit,
+    },
+    query::{QueryFilter, SpatialQuery},
+    ragdoll::{Ragdoll, RagdollBuilder, RagdollMode},
+    vehicle::{Vehicle, VehicleInput},
 };
 
 #[derive(Debug)]
@@ -25,162 +65,1213 @@ impl Default for PhysicsPlugin {
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        // TODO: Fix order dependencies using system sets
-
         app.insert_resource(PhysicsParameters::default())
             .insert_resource(InternalParameters {
                 substeps: self.substeps,
             })
+            .init_resource::<BroadPhase>()
+            .init_resource::<Collisions>()
+            .init_resource::<Contacts>()
+            .add_event::<Explosion>()
+            .add_event::<TriggerEnter>()
+            .add_event::<TriggerExit>()
+            .add_event::<CollisionStarted>()
+            .add_event::<CollisionEnded>()
+            .configure_sets(
+                Update,
+                (PhysicsSet::Prepare, PhysicsSet::Step, PhysicsSet::Finalize).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    build_ragdolls,
+                    infer_rigid_bodies,
+                    explosions,
+                    prepare_vehicles,
+                    apply_deferred,
+                    validate_rigid_bodies,
+                    validate_springs,
+                    animate_ragdolls,
+                    apply_external_impulses,
+                    characters,
+                    vehicles,
+                    kinematic_velocities,
+                    update_broad_phase,
+                    begin_collisions,
+                )
+                    .chain()
+                    .in_set(PhysicsSet::Prepare),
+            )
+            .add_systems(Update, run_substeps.in_set(PhysicsSet::Step))
+            .add_systems(
+                Update,
+                (clear_external_forces, collision_events, pose_ragdolls)
+                    .in_set(PhysicsSet::Finalize),
+            )
             .add_systems(
                 Update,
-                debug_bodies.run_if(|param: Res<PhysicsParameters>| param.debug),
+                (debug_bodies, debug_joints, debug_vehicles)
+                    .run_if(|param: Res<PhysicsParameters>| param.debug),
             );
 
-        for _ in 0..self.substeps {
-            app.add_systems(
-                Update,
+        let mut substep = Schedule::new(Substep);
+        substep
+            .configure_sets(
+                (
+                    SubstepSet::Integrate,
+                    SubstepSet::NarrowPhase,
+                    SubstepSet::ModifyContacts,
+                    SubstepSet::Solve,
+                    SubstepSet::Derive,
+                )
+                    .chain(),
+            )
+            .add_systems(
                 (
+                    force_fields,
+                    fluids,
                     integrate_translation,
                     integrate_rotation,
-                    contacts,
-                    derive_translation,
-                    derive_rotation,
+                    sweep_fast_bodies,
                 )
-                    .chain(),
+                    .chain()
+                    .in_set(SubstepSet::Integrate),
+            )
+            .add_systems(narrow_phase.in_set(SubstepSet::NarrowPhase))
+            .add_systems((solve_contacts, joints).chain().in_set(SubstepSet::Solve))
+            .add_systems(
+                (derive_translation, derive_rotation)
+                    .chain()
+                    .in_set(SubstepSet::Derive),
             );
-        }
+        app.add_schedule(substep);
     }
 }
 
+/// The stages of a physics step, running in order during [`Update`].
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PhysicsSet {
+    /// Runs once before the substeps.
+    Prepare,
+    /// Runs the [`Substep`] schedule for each substep.
+    Step,
+    /// Runs once after the substeps.
+    Finalize,
+}
+
+/// The schedule which is run once per substep.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Substep;
+
+/// The stages of a substep, running in order during [`Substep`].
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SubstepSet {
+    /// Predicts the new poses of all bodies.
+    Integrate,
+    /// Finds the [`Contacts`] between colliders at their predicted poses.
+    NarrowPhase,
+    /// Empty, for user systems altering or disabling [`Contacts`] before they are solved.
+    ModifyContacts,
+    /// Corrects the predicted poses to satisfy contacts and joints.
+    Solve,
+    /// Derives velocities from the corrected poses.
+    Derive,
+}
+
 #[derive(Resource)]
 struct InternalParameters {
     substeps: usize,
 }
 
+impl InternalParameters {
+    /// The duration of a single substep.
+    fn dt(&self, parameters: &PhysicsParameters) -> f32 {
+        self.step_dt(parameters) / self.substeps as f32
+    }
+
+    /// The duration of a whole step.
+    fn step_dt(&self, parameters: &PhysicsParameters) -> f32 {
+        1.0 / parameters.frequency / parameters.time_scale
+    }
+}
+
 #[derive(Resource)]
 pub struct PhysicsParameters {
     pub debug: bool,
-    pub gravity: f32,
+    pub gravity: Vec3,
+    /// Used for bodies without [`LinearDamping`].
+    pub linear_damping: f32,
+    /// Used for bodies without [`AngularDamping`].
+    pub angular_damping: f32,
     pub stiffness: f32,
+    /// The friction coefficient new contacts start with.
+    pub friction: f32,
+    /// The distance within which contacts are found before colliders touch,
+    /// so they are caught earlier and resting contacts persist.
+    /// Used for colliders without [`ContactPrediction`].
+    pub contact_prediction: f32,
+    /// The number of steps per simulated second. Each step advances by `1 / frequency`,
+    /// independently of the frame time.
     pub frequency: f32,
     pub time_scale: f32,
+    /// Makes the results bit-identical for identical inputs, regardless of how entities are stored,
+    /// by solving contacts and joints in the order of their entities.
+    /// Combine with the `libm` feature for identical results across platforms.
+    pub deterministic: bool,
 }
 
 impl Default for PhysicsParameters {
     fn default() -> Self {
         Self {
             debug: false,
-            gravity: 10.0,
+            gravity: Vec3::new(0.0, -10.0, 0.0),
+            linear_damping: 0.0,
+            angular_damping: 0.0,
             stiffness: 1.0,
+            friction: 0.5,
+            contact_prediction: 0.02,
             frequency: 60.0,
             time_scale: 1.0,
+            deterministic: false,
         }
     }
 }
 
-fn integrate_translation(
-    mut query: Query<(&mut Linear, &Collider, &mut Transform)>,
+#[allow(clippy::type_complexity)]
+fn build_ragdolls(
+    mut commands: Commands,
+    builders: Query<(Entity, &RagdollBuilder, &SkinnedMesh)>,
+    joints: Query<(Ref<GlobalTransform>, Option<&Name>, Option<&Parent>)>,
+) {
+    for (entity, builder, skinned_mesh) in builders.iter() {
+        // Freshly spawned skeletons are not posed until their transforms have been propagated.
+        if skinned_mesh.joints.iter().any(|&joint| {
+            joints
+                .get(joint)
+                .map_or(true, |(global_transform, ..)| global_transform.is_added())
+        }) {
+            continue;
+        }
+
+        let ragdoll = builder.build(&mut commands, &skinned_mesh.joints, &joints);
+        commands
+            .entity(entity)
+            .remove::<RagdollBuilder>()
+            .insert(ragdoll);
+    }
+}
+
+/// Switches the bones of ragdolls between kinematic and dynamic bodies
+/// and moves those of animated ragdolls to their joints.
+///
+/// The joints are read from their [`GlobalTransform`]s, which lag a frame behind the animation.
+fn animate_ragdolls(
+    ragdolls: Query<&Ragdoll>,
+    mut bodies: Query<(&mut RigidBody, &mut Transform)>,
+    joints: Query<&GlobalTransform>,
+) {
+    for ragdoll in ragdolls.iter() {
+        for bone in ragdoll.bones() {
+            let Ok((mut rigid_body, mut transform)) = bodies.get_mut(bone.body) else {
+                continue;
+            };
+            rigid_body.set_if_neq(ragdoll.mode.rigid_body());
+
+            if ragdoll.mode == RagdollMode::Animated {
+                if let Ok(joint) = joints.get(bone.joint) {
+                    *transform = GlobalTransform::from(
+                        joint.affine() * bone.offset.compute_affine().inverse(),
+                    )
+                    .compute_transform();
+                }
+            }
+        }
+    }
+}
+
+/// Poses the joints of simulated ragdolls after their bones.
+fn pose_ragdolls(
+    ragdolls: Query<&Ragdoll>,
+    mut transforms: Query<&mut Transform>,
+    joints: Query<&GlobalTransform>,
+) {
+    for ragdoll in ragdolls.iter() {
+        if ragdoll.mode != RagdollMode::Simulated {
+            continue;
+        }
+
+        // Bones come after their parents, whose new poses are not yet propagated.
+        let mut poses = HashMap::new();
+        for bone in ragdoll.bones() {
+            let Ok(body) = transforms.get(bone.body) else {
+                continue;
+            };
+            let pose = GlobalTransform::from(*body) * bone.offset;
+            let parent = bone.parent.map_or(GlobalTransform::IDENTITY, |parent| {
+                poses
+                    .get(&parent)
+                    .or_else(|| joints.get(parent).ok())
+                    .copied()
+                    .unwrap_or_default()
+            });
+            if let Ok(mut transform) = transforms.get_mut(bone.joint) {
+                *transform = pose.reparented_to(&parent);
+            }
+            poses.insert(bone.joint, pose);
+        }
+    }
+}
+
+/// Remembers the [`RigidBody`] inferred for a body, which is not inferred again once set to anything else.
+#[derive(Component)]
+struct InferredRigidBody(RigidBody);
+
+/// Infers the kind of colliders spawned without a [`RigidBody`].
+/// Inferred kinds follow later changes to the motion or mass until a different kind is set explicitly.
+#[allow(clippy::type_complexity)]
+fn infer_rigid_bodies(
+    mut commands: Commands,
+    uninferred: Query<(Entity, &Collider, Has<Linear>), Without<RigidBody>>,
+    mut inferred: Query<(
+        Entity,
+        &Collider,
+        &mut RigidBody,
+        &mut InferredRigidBody,
+        Has<Linear>,
+    )>,
+) {
+    for (entity, collider, linear) in uninferred.iter() {
+        let kind = RigidBody::inferred(collider, linear);
+        commands
+            .entity(entity)
+            .insert((kind, InferredRigidBody(kind)));
+    }
+
+    for (entity, collider, mut rigid_body, mut last, linear) in inferred.iter_mut() {
+        if *rigid_body != last.0 {
+            commands.entity(entity).remove::<InferredRigidBody>();
+            continue;
+        }
+        let kind = RigidBody::inferred(collider, linear);
+        rigid_body.set_if_neq(kind);
+        last.0 = kind;
+    }
+}
+
+/// Warns about bodies whose kind does not fit their motion or mass.
+#[allow(clippy::type_complexity)]
+fn validate_rigid_bodies(
+    changed: Query<(Entity, &Collider, &RigidBody, Has<Linear>, Has<Angular>), Changed<RigidBody>>,
+) {
+    for (entity, collider, rigid_body, linear, angular) in changed.iter() {
+        match rigid_body {
+            RigidBody::Dynamic if !linear || !angular => {
+                warn!("Dynamic body {entity:?} lacks linear or angular motion and will only partially move")
+            }
+            RigidBody::Dynamic if collider.inv_mass() == 0.0 => {
+                warn!("Dynamic body {entity:?} has an infinite mass, consider making it static")
+            }
+            RigidBody::Static if linear || angular => {
+                warn!("Static body {entity:?} has linear or angular motion, which is ignored")
+            }
+            RigidBody::Kinematic if !linear || !angular => {
+                warn!("Kinematic body {entity:?} lacks linear or angular motion and will not push other bodies")
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Warns about springs with negative coefficients, which are treated as zero.
+fn validate_springs(changed: Query<(Entity, &SpringJoint), Changed<SpringJoint>>) {
+    for (entity, spring) in changed.iter() {
+        if spring.stiffness < 0.0 || spring.damping < 0.0 {
+            warn!("Spring joint {entity:?} has a negative stiffness or damping, which is treated as zero");
+        }
+    }
+}
+
+/// Gives vehicles non-persistent external forces and torques, to which their wheel forces are added every step.
+#[allow(clippy::type_complexity)]
+fn prepare_vehicles(
+    mut commands: Commands,
+    mut vehicles: Query<
+        (
+            Entity,
+            Option<&mut ExternalForce>,
+            Option<&mut ExternalTorque>,
+        ),
+        (
+            With<Vehicle>,
+            Or<(
+                Added<Vehicle>,
+                Changed<ExternalForce>,
+                Changed<ExternalTorque>,
+            )>,
+        ),
+    >,
+) {
+    for (entity, force, torque) in vehicles.iter_mut() {
+        let persistent = force.as_ref().is_some_and(|force| force.persistent)
+            || torque.as_ref().is_some_and(|torque| torque.persistent);
+        if persistent {
+            warn!("Vehicle {entity:?} has a persistent external force or torque, which is made non-persistent");
+        }
+        match force {
+            Some(mut force) if force.persistent => force.persistent = false,
+            Some(_) => {}
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(ExternalForce::default().persistent(false));
+            }
+        }
+        match torque {
+            Some(mut torque) if torque.persistent => torque.persistent = false,
+            Some(_) => {}
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(ExternalTorque::default().persistent(false));
+            }
+        }
+    }
+}
+
+fn explosions(
+    mut commands: Commands,
+    mut explosions: EventReader<Explosion>,
+    spatial_query: SpatialQuery,
+    query: Query<(&Collider, &Transform, &RigidBody), With<Linear>>,
+) {
+    for explosion in explosions.read() {
+        let blast = Transform::from_translation(explosion.center);
+        let blast_shape = Shape::Ball {
+            radius: explosion.radius,
+        };
+        let filter = QueryFilter::default()
+            .mask(explosion.mask)
+            .exclude_sensors();
+
+        for entity in spatial_query.shape_intersections(&blast_shape, &blast, &filter) {
+            let Ok((collider, transform, rigid_body)) = query.get(entity) else {
+                continue;
+            };
+            if collider.inv_mass() == 0.0 || !rigid_body.is_dynamic() {
+                continue;
+            }
+
+            let (point, inside) = project_point(&collider.shape, transform, explosion.center);
+            let (point, offset) = if inside {
+                (explosion.center, transform.translation - explosion.center)
+            } else {
+                (point, point - explosion.center)
+            };
+            let falloff = (1.0 - offset.length() / explosion.radius).max(0.0);
+
+            commands.entity(entity).apply_impulse(
+                explosion.strength * falloff * offset.normalize_or_zero(),
+                point,
+            );
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_external_impulses(
+    mut query: Query<(
+        &mut ExternalImpulse,
+        &Collider,
+        &Transform,
+        Option<&mut Linear>,
+        Option<&mut Angular>,
+        &RigidBody,
+    )>,
+) {
+    for (mut external_impulse, collider, transform, linear, angular, rigid_body) in query.iter_mut()
+    {
+        if !rigid_body.is_dynamic() {
+            *external_impulse = ExternalImpulse::default();
+            continue;
+        }
+        if let Some(mut linear) = linear {
+            linear.receive_impulse(external_impulse.impulse, collider.inv_mass());
+        }
+        if let Some(mut angular) = angular {
+            angular.receive_impulse(
+                transform.rotation,
+                external_impulse.angular_impulse,
+                collider.inv_moment_of_inertia(),
+            );
+        }
+        *external_impulse = ExternalImpulse::default();
+    }
+}
+
+/// Moves character controllers and pushes the dynamic bodies they run into.
+#[allow(clippy::type_complexity)]
+fn characters(
+    mut commands: Commands,
+    mut set: ParamSet<(
+        SpatialQuery,
+        Query<(
+            Entity,
+            &mut CharacterController,
+            &mut CharacterInput,
+            &Collider,
+            &mut Transform,
+            Option<&CollisionLayers>,
+        )>,
+    )>,
+    bodies: Query<(&RigidBody, &Linear)>,
     parameters: Res<PhysicsParameters>,
     internal: Res<InternalParameters>,
 ) {
-    let dt = 1.0 / parameters.frequency / internal.substeps as f32 / parameters.time_scale;
+    let dt = internal.step_dt(&parameters);
 
-    for (mut linear, collider, transform) in query.iter_mut() {
-        linear.integrate(
-            transform.translation,
-            Vec3::new(0.0, -parameters.gravity, 0.0),
-            collider.inv_mass(),
+    // Characters are moved one after another, each seeing the others where they were before.
+    let characters = ordered(
+        set.p1()
+            .iter()
+            .map(|(entity, controller, input, collider, transform, layers)| {
+                let character = (
+                    entity,
+                    *controller,
+                    *input,
+                    collider.shape,
+                    *transform,
+                    layers.copied().unwrap_or_default(),
+                );
+                (entity, character)
+            }),
+        parameters.deterministic,
+    );
+
+    for (entity, mut controller, mut input, shape, transform, layers) in characters {
+        let filter = QueryFilter::default()
+            .exclude(entity)
+            .mask(layers.filters)
+            .exclude_sensors();
+        let (translation, pushes) = controller.step(
+            &mut input,
+            &shape,
+            &transform,
+            &set.p0(),
+            &filter,
+            parameters.gravity,
             dt,
         );
+
+        for push in pushes {
+            let Ok((rigid_body, linear)) = bodies.get(push.hit.entity) else {
+                continue;
+            };
+            let approach = (push.velocity - linear.velocity).dot(-push.hit.normal);
+            if rigid_body.is_dynamic() && approach > 0.0 {
+                commands.entity(push.hit.entity).apply_impulse(
+                    -controller.mass * approach * push.hit.normal,
+                    push.hit.point,
+                );
+            }
+        }
+
+        let mut characters = set.p1();
+        let Ok((_, mut old_controller, mut old_input, _, mut old_transform, _)) =
+            characters.get_mut(entity)
+        else {
+            continue;
+        };
+        *old_controller = controller;
+        *old_input = input;
+        old_transform.translation = translation;
     }
 }
 
-fn integrate_rotation(
-    mut query: Query<(&mut Angular, &Collider, &mut Transform)>,
+/// Applies the suspension and tyre forces of vehicles to their chassis.
+#[allow(clippy::type_complexity)]
+fn vehicles(
+    mut query: Query<(
+        Entity,
+        &mut Vehicle,
+        &VehicleInput,
+        &Transform,
+        &Linear,
+        &Angular,
+        &Collider,
+        &mut ExternalForce,
+        &mut ExternalTorque,
+        Option<&CollisionLayers>,
+    )>,
+    spatial_query: SpatialQuery,
     parameters: Res<PhysicsParameters>,
     internal: Res<InternalParameters>,
 ) {
-    let dt = 1.0 / parameters.frequency / internal.substeps as f32 / parameters.time_scale;
+    let dt = internal.step_dt(&parameters);
 
-    for (mut angular, collider, transform) in query.iter_mut() {
-        angular.integrate(transform.rotation, collider.inv_moment_of_inertia(), dt);
+    for (
+        entity,
+        mut vehicle,
+        input,
+        transform,
+        linear,
+        angular,
+        collider,
+        mut force,
+        mut torque,
+        layers,
+    ) in query.iter_mut()
+    {
+        let filter = QueryFilter::default()
+            .exclude(entity)
+            .mask(layers.copied().unwrap_or_default().filters)
+            .exclude_sensors();
+        for (wheel_force, point) in vehicle.forces(
+            input,
+            transform,
+            linear.velocity,
+            angular.angular_velocity,
+            collider.mass,
+            &spatial_query,
+            &filter,
+            dt,
+        ) {
+            force.apply_at_point(&mut torque, wheel_force, point, transform.translation);
+        }
     }
 }
 
-fn contacts(
-    mut query: Query<(
+/// Infers the velocities of kinematic bodies from the difference between their user-set [`Transform`]
+/// and the pose they ended the previous step with, then rewinds them to that pose
+/// so the substeps sweep them along.
+fn kinematic_velocities(
+    mut query: Query<(Ref<RigidBody>, &mut Transform, &mut Linear, &mut Angular)>,
+    parameters: Res<PhysicsParameters>,
+    internal: Res<InternalParameters>,
+) {
+    let dt = internal.step_dt(&parameters);
+
+    for (rigid_body, mut transform, mut linear, mut angular) in query.iter_mut() {
+        if *rigid_body != RigidBody::Kinematic {
+            continue;
+        }
+        if rigid_body.is_added() {
+            linear.translation = transform.translation;
+            angular.rotation = transform.rotation;
+        }
+
+        let (translation, rotation) = (linear.translation, angular.rotation);
+        linear.translation = transform.translation;
+        angular.rotation = transform.rotation;
+        linear.derive(translation, dt);
+        angular.derive(rotation, dt);
+        transform.translation = translation;
+        transform.rotation = rotation;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_broad_phase(
+    mut broad_phase: ResMut<BroadPhase>,
+    query: Query<(
+        Entity,
+        &Collider,
         &Transform,
+        Option<&Linear>,
+        Option<&CollisionLayers>,
+        Option<&ContactPrediction>,
+    )>,
+    parameters: Res<PhysicsParameters>,
+    internal: Res<InternalParameters>,
+) {
+    let dt = internal.step_dt(&parameters);
+
+    broad_phase.update(
+        query.iter().map(
+            |(entity, collider, transform, linear, layers, prediction)| {
+                let motion = linear.map_or(Vec3::ZERO, |linear| dt * linear.velocity);
+                let prediction =
+                    prediction.map_or(parameters.contact_prediction, |prediction| prediction.0);
+                (
+                    entity,
+                    swept_aabb(&collider.shape, transform, motion).loosened(prediction),
+                    layers.copied().unwrap_or_default(),
+                )
+            },
+        ),
+        parameters.deterministic,
+    );
+}
+
+fn begin_collisions(mut collisions: ResMut<Collisions>) {
+    collisions.begin_step();
+}
+
+fn run_substeps(world: &mut World) {
+    let substeps = world.resource::<InternalParameters>().substeps;
+    for _ in 0..substeps {
+        world.run_schedule(Substep);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn force_fields(
+    mut bodies: Query<(
+        &mut Linear,
         &Collider,
-        Option<&mut Linear>,
-        Option<&mut Angular>,
+        &Transform,
+        &RigidBody,
+        Option<&CollisionLayers>,
     )>,
+    fields: Query<
+        (
+            Entity,
+            &ForceField,
+            &Collider,
+            &Transform,
+            Option<&CollisionLayers>,
+        ),
+        With<Sensor>,
+    >,
     parameters: Res<PhysicsParameters>,
-    mut gizmos: Gizmos,
+    internal: Res<InternalParameters>,
 ) {
-    let mut combinations = query.iter_combinations_mut();
-    while let Some(
-        [(past_0, collider_0, mut linear_0, mut angular_0), (past_1, collider_1, mut linear_1, mut angular_1)],
-    ) = combinations.fetch_next()
+    let dt = internal.dt(&parameters);
+    // The effects of overlapping fields add up in the same order regardless of how they are stored.
+    let fields = ordered(
+        fields
+            .iter()
+            .map(|(entity, field, collider, transform, layers)| {
+                (entity, (field, collider, transform, layers))
+            }),
+        parameters.deterministic,
+    );
+
+    for (mut linear, collider, transform, rigid_body, layers) in bodies.iter_mut() {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let layers = layers.copied().unwrap_or_default();
+        for &(field, field_collider, field_transform, field_layers) in &fields {
+            if !layers.interacts(&field_layers.copied().unwrap_or_default())
+                || !intersects(
+                    (&field_collider.shape, &collider.shape),
+                    (field_transform, transform),
+                )
+            {
+                continue;
+            }
+
+            let effect = field.evaluate(field_transform, transform.translation);
+            match field.mode {
+                FieldMode::Acceleration => {
+                    linear.accelerate(effect, Vec3::ZERO, collider.inv_mass(), dt)
+                }
+                FieldMode::Force => linear.accelerate(Vec3::ZERO, effect, collider.inv_mass(), dt),
+            }
+        }
+    }
+}
+
+fn fluids(
+    mut bodies: Query<(&mut Linear, &mut Angular, &Collider, &Transform, &RigidBody)>,
+    fluids: Query<(Entity, &FluidVolume, &Transform)>,
+    parameters: Res<PhysicsParameters>,
+    internal: Res<InternalParameters>,
+) {
+    let dt = internal.dt(&parameters);
+    if fluids.is_empty() {
+        return;
+    }
+    let fluids = ordered(
+        fluids
+            .iter()
+            .map(|(entity, fluid, transform)| (entity, (fluid, transform))),
+        parameters.deterministic,
+    );
+
+    for (mut linear, mut angular, collider, transform, rigid_body) in bodies.iter_mut() {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let shape = collider.shape.parry_shape();
+        let inv_moment_of_inertia = collider.inv_moment_of_inertia();
+        for &(fluid, fluid_transform) in &fluids {
+            let Some(submersion) = fluid.submersion(fluid_transform, &*shape, transform) else {
+                continue;
+            };
+
+            let buoyancy = -fluid.density * submersion.volume * parameters.gravity;
+            let drag = -fluid.linear_drag * submersion.fraction * linear.velocity;
+            linear.accelerate(Vec3::ZERO, buoyancy + drag, collider.inv_mass(), dt);
+
+            let torque = (submersion.center_of_buoyancy - transform.translation).cross(buoyancy)
+                - fluid.angular_drag * submersion.fraction * angular.angular_velocity;
+            angular.receive_impulse(transform.rotation, dt * torque, inv_moment_of_inertia);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn integrate_translation(
+    mut query: Query<(
+        &mut Linear,
+        &Collider,
+        &mut Transform,
+        Option<&ExternalForce>,
+        Option<&GravityScale>,
+        Option<&LinearDamping>,
+        Option<&LockedAxes>,
+        &RigidBody,
+    )>,
+    parameters: Res<PhysicsParameters>,
+    internal: Res<InternalParameters>,
+) {
+    let dt = internal.dt(&parameters);
+
+    for (
+        mut linear,
+        collider,
+        transform,
+        external_force,
+        gravity_scale,
+        damping,
+        locked_axes,
+        rigid_body,
+    ) in query.iter_mut()
+    {
+        match rigid_body {
+            RigidBody::Dynamic => {
+                linear.damp(
+                    damping.map_or(parameters.linear_damping, |damping| damping.0),
+                    dt,
+                );
+                linear.integrate(
+                    transform.translation,
+                    gravity_scale.map_or(1.0, |gravity_scale| gravity_scale.0) * parameters.gravity,
+                    external_force.map_or(Vec3::ZERO, |external_force| external_force.force),
+                    collider.inv_mass(),
+                    locked_axes.map_or(BVec3::FALSE, |locked_axes| locked_axes.translation),
+                    dt,
+                );
+            }
+            RigidBody::Static => {
+                linear.velocity = Vec3::ZERO;
+                linear.integrate(
+                    transform.translation,
+                    Vec3::ZERO,
+                    Vec3::ZERO,
+                    0.0,
+                    BVec3::FALSE,
+                    dt,
+                );
+            }
+            RigidBody::Kinematic => {
+                linear.integrate(
+                    transform.translation,
+                    Vec3::ZERO,
+                    Vec3::ZERO,
+                    0.0,
+                    BVec3::FALSE,
+                    dt,
+                );
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn integrate_rotation(
+    mut query: Query<(
+        &mut Angular,
+        &Collider,
+        &mut Transform,
+        Option<&ExternalTorque>,
+        Option<&AngularDamping>,
+        Option<&LockedAxes>,
+        &RigidBody,
+    )>,
+    parameters: Res<PhysicsParameters>,
+    internal: Res<InternalParameters>,
+) {
+    let dt = internal.dt(&parameters);
+
+    for (mut angular, collider, transform, external_torque, damping, locked_axes, rigid_body) in
+        query.iter_mut()
     {
-        let translation_0 = match &linear_0 {
-            Some(linear) => linear.translation,
-            None => past_0.translation,
+        match rigid_body {
+            RigidBody::Dynamic => {
+                angular.damp(
+                    damping.map_or(parameters.angular_damping, |damping| damping.0),
+                    dt,
+                );
+                angular.integrate(
+                    transform.rotation,
+                    external_torque.map_or(Vec3::ZERO, |external_torque| external_torque.torque),
+                    collider.inv_moment_of_inertia(),
+                    locked_axes.map_or(BVec3::FALSE, |locked_axes| locked_axes.rotation),
+                    dt,
+                );
+            }
+            RigidBody::Static => {
+                angular.angular_velocity = Vec3::ZERO;
+                angular.integrate(transform.rotation, Vec3::ZERO, Vec3::ZERO, BVec3::FALSE, dt);
+            }
+            RigidBody::Kinematic => {
+                angular.integrate(transform.rotation, Vec3::ZERO, Vec3::ZERO, BVec3::FALSE, dt);
+            }
+        }
+    }
+}
+
+/// Clamps fast bodies with [`Ccd`] to their first impact between their previous and predicted poses.
+/// Only the part of their motion running into the hit surface is stopped, the rest slides along it.
+/// The sweep only covers translation, so bodies keep their predicted rotation,
+/// which is corrected by the contacts like that of slower bodies.
+#[allow(clippy::type_complexity)]
+fn sweep_fast_bodies(
+    mut query: Query<(
+        &Collider,
+        &Transform,
+        Option<&mut Linear>,
+        &RigidBody,
+        Has<Sensor>,
+        Has<Ccd>,
+    )>,
+    broad_phase: Res<BroadPhase>,
+) {
+    // Visited in entity order, so the sweep does not depend on hashing.
+    let mut impacts: BTreeMap<Entity, (f32, Vec3)> = BTreeMap::new();
+
+    for &(entity_0, entity_1) in broad_phase.pairs() {
+        let Ok(
+            [(collider_0, past_0, linear_0, rigid_body_0, sensor_0, ccd_0), (collider_1, past_1, linear_1, rigid_body_1, sensor_1, ccd_1)],
+        ) = query.get_many([entity_0, entity_1])
+        else {
+            continue;
         };
-        let translation_1 = match &linear_1 {
-            Some(linear) => linear.translation,
-            None => past_1.translation,
+        if sensor_0 || sensor_1 {
+            continue;
+        }
+
+        let motion_0 =
+            linear_0.map_or(Vec3::ZERO, |linear| linear.translation - past_0.translation);
+        let motion_1 =
+            linear_1.map_or(Vec3::ZERO, |linear| linear.translation - past_1.translation);
+        let sweeps = |ccd: bool, rigid_body: &RigidBody, collider: &Collider, motion: Vec3| {
+            ccd && rigid_body.is_dynamic() && motion.length() > collider.shape.inner_radius()
         };
-        let rotation_0 = match &angular_0 {
-            Some(angular) => angular.rotation,
-            None => past_0.rotation,
+        let sweep_0 = sweeps(ccd_0, rigid_body_0, collider_0, motion_0);
+        let sweep_1 = sweeps(ccd_1, rigid_body_1, collider_1, motion_1);
+        if !sweep_0 && !sweep_1 {
+            continue;
+        }
+
+        let Some(impact) = time_of_impact(
+            (&collider_0.shape, &collider_1.shape),
+            (past_0, past_1),
+            (motion_0, motion_1),
+        ) else {
+            continue;
         };
-        let rotation_1 = match &angular_1 {
-            Some(angular) => angular.rotation,
-            None => past_1.rotation,
+        for (entity, sweep, normal) in [
+            (entity_0, sweep_0, -impact.normals.0),
+            (entity_1, sweep_1, -impact.normals.1),
+        ] {
+            let earliest = !impacts
+                .get(&entity)
+                .is_some_and(|(time, _)| *time <= impact.time);
+            if sweep && earliest {
+                impacts.insert(entity, (impact.time, normal));
+            }
+        }
+    }
+
+    // The remaining motion slides along the surface, so bodies skimming it are not stopped.
+    for (entity, (time, normal)) in impacts {
+        let Ok((_, past, Some(mut linear), ..)) = query.get_mut(entity) else {
+            continue;
         };
+        let remaining = (1.0 - time) * (linear.translation - past.translation);
+        linear.translation -= remaining.dot(normal).min(0.0) * normal;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn narrow_phase(
+    query: Query<(
+        &Transform,
+        &Collider,
+        &RigidBody,
+        Has<Sensor>,
+        Option<&ContactPrediction>,
+    )>,
+    broad_phase: Res<BroadPhase>,
+    mut contacts: ResMut<Contacts>,
+    parameters: Res<PhysicsParameters>,
+) {
+    contacts.clear();
 
-        let _future_0 = Transform {
-            translation: translation_0,
-            rotation: rotation_0,
-            scale: Vec3::ONE,
+    for &(entity_0, entity_1) in broad_phase.pairs() {
+        let Ok(
+            [(past_0, collider_0, rigid_body_0, sensor_0, prediction_0), (past_1, collider_1, rigid_body_1, sensor_1, prediction_1)],
+        ) = query.get_many([entity_0, entity_1])
+        else {
+            continue;
         };
-        let _future_1 = Transform {
-            translation: translation_1,
-            rotation: rotation_1,
-            scale: Vec3::ONE,
+        if *rigid_body_0 == RigidBody::Static && *rigid_body_1 == RigidBody::Static {
+            continue;
+        }
+
+        let prediction = |prediction: Option<&ContactPrediction>| {
+            prediction.map_or(parameters.contact_prediction, |prediction| prediction.0)
         };
 
         if let Some(contact) = contact(
             (collider_0, collider_1),
-            (translation_0, translation_1),
-            (rotation_0, rotation_1),
+            (past_0.translation, past_1.translation),
+            (past_0.rotation, past_1.rotation),
+            prediction(prediction_0).max(prediction(prediction_1)),
         ) {
-            let mut _correction_1 = 0.5 * contact.depth * contact.normals.1;
+            contacts.push(ContactPair {
+                entities: (entity_0, entity_1),
+                points: contact.points,
+                normal: contact.normals.0,
+                depth: contact.depth,
+                enabled: true,
+                friction: parameters.friction,
+                surface_velocity: Vec3::ZERO,
+                sensors: (sensor_0, sensor_1),
+            });
+        }
+    }
+}
 
-            if let Some(linear) = &mut linear_0 {
-                linear.push_impulse(parameters.stiffness * 0.5 * contact.depth * contact.normals.0);
-            }
-            if let Some(linear) = &mut linear_1 {
-                linear.push_impulse(parameters.stiffness * 0.5 * contact.depth * contact.normals.1);
-            }
-            if let Some(angular) = &mut angular_0 {
-                angular.push_impulse(
-                    contact.points.0,
-                    translation_0,
-                    parameters.stiffness * 0.5 * contact.depth * contact.normals.0,
-                );
-            }
-            if let Some(angular) = &mut angular_1 {
-                angular.push_impulse(
-                    contact.points.1,
-                    translation_1,
-                    parameters.stiffness * 0.5 * contact.depth * contact.normals.1,
-                );
+#[allow(clippy::type_complexity)]
+fn solve_contacts(
+    mut query: Query<(&Transform, Option<&mut Linear>, Option<&mut Angular>), With<Collider>>,
+    contacts: Res<Contacts>,
+    mut collisions: ResMut<Collisions>,
+    parameters: Res<PhysicsParameters>,
+    internal: Res<InternalParameters>,
+    mut gizmos: Gizmos,
+) {
+    let dt = internal.dt(&parameters);
+
+    for pair in contacts.iter().filter(|pair| pair.enabled) {
+        let normals = (pair.normal, -pair.normal);
+        if pair.is_sensor() {
+            if !pair.is_speculative() {
+                collisions.insert(pair.entities, pair.sensors, pair.points, normals, 0.0);
             }
+            continue;
+        }
+
+        let Ok([(past_0, mut linear_0, mut angular_0), (past_1, mut linear_1, mut angular_1)]) =
+            query.get_many_mut([pair.entities.0, pair.entities.1])
+        else {
+            continue;
+        };
+
+        // The contact points were found at the start of the substep and move along with the bodies.
+        let attachment_0 = attachment(
+            past_0,
+            linear_0.as_deref(),
+            angular_0.as_deref(),
+            past_0.rotation.conjugate() * (pair.points.0 - past_0.translation),
+        );
+        let attachment_1 = attachment(
+            past_1,
+            linear_1.as_deref(),
+            angular_1.as_deref(),
+            past_1.rotation.conjugate() * (pair.points.1 - past_1.translation),
+        );
+
+        // Speculative contacts are inequalities: the colliders may approach each other up to the gap
+        // between them, so a fast approach is stopped before they pass through each other.
+        let separation = pair.depth
+            + ((attachment_1.point - attachment_1.past_point)
+                - (attachment_0.point - attachment_0.past_point))
+                .dot(pair.normal);
+        if separation > 0.0 {
+            continue;
+        }
+        let w = attachment_0.generalized_inv_mass(pair.normal)
+            + attachment_1.generalized_inv_mass(pair.normal);
+        if w <= 0.0 {
+            // Neither body can be pushed, e.g. a kinematic body touching a static one, but they still touch.
+            collisions.insert(pair.entities, pair.sensors, pair.points, normals, 0.0);
+            continue;
+        }
+        let normal_lambda = -parameters.stiffness * separation / w;
+
+        // The impulse pushes the second body, the first one receives the negation.
+        let mut impulse = normal_lambda * pair.normal;
+
+        // Friction counteracts the tangential slip of the second body relative to the first one's surface,
+        // limited by the Coulomb cone.
+        let slip = (attachment_1.point - attachment_1.past_point)
+            - (attachment_0.point - attachment_0.past_point)
+            - dt * pair.surface_velocity;
+        let tangential_slip = slip - slip.dot(pair.normal) * pair.normal;
+        if let Some(tangent) = tangential_slip.try_normalize() {
+            let w = attachment_0.generalized_inv_mass(tangent)
+                + attachment_1.generalized_inv_mass(tangent);
+            let friction_lambda = (tangential_slip.length() / w).min(pair.friction * normal_lambda);
+            impulse -= friction_lambda * tangent;
+        }
+
+        if let Some(linear) = &mut linear_0 {
+            linear.push_impulse(-impulse);
+        }
+        if let Some(linear) = &mut linear_1 {
+            linear.push_impulse(impulse);
+        }
+        if let Some(angular) = &mut angular_0 {
+            angular.push_impulse(attachment_0.point, attachment_0.center_of_mass, -impulse);
+        }
+        if let Some(angular) = &mut angular_1 {
+            angular.push_impulse(attachment_1.point, attachment_1.center_of_mass, impulse);
+        }
+
+        collisions.insert(
+            pair.entities,
+            pair.sensors,
+            pair.points,
+            normals,
+            normal_lambda / dt,
+        );
+        debug_contact(&mut gizmos, pair.points, &parameters);
+    }
+}
+
+/// The bodies joints act on.
+type JointBodies<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        Option<&'static mut Linear>,
+        Option<&'static mut Angular>,
+    ),
+    With<Collider>,
+>;
+
+fn joints(
+    mut bodies: JointBodies,
+    springs: Query<(Entity, &SpringJoint)>,
+    sphericals: Query<(Entity, &SphericalJoint)>,
+    hinges: Query<(Entity, &HingeJoint)>,
+    parameters: Res<PhysicsParameters>,
+    internal: Res<InternalParameters>,
+) {
+    let dt = internal.dt(&parameters);
+    let deterministic = parameters.deterministic;
+
+    for spring in ordered(springs.iter(), deterministic) {
+        solve_joint(
+            &mut bodies,
+            spring.bodies,
+            spring.anchors,
+            |attachments, _| JointImpulses {
+                impulse: spring_impulse(spring, attachments, dt),
+                ..default()
+            },
+        );
+    }
+    for joint in ordered(sphericals.iter(), deterministic) {
+        solve_joint(
+            &mut bodies,
+            joint.bodies,
+            joint.anchors,
+            |attachments, rotations| spherical_impulses(joint, attachments, rotations),
+        );
+    }
+    for joint in ordered(hinges.iter(), deterministic) {
+        solve_joint(
+            &mut bodies,
+            joint.bodies,
+            joint.anchors,
+            |attachments, rotations| hinge_impulses(joint, attachments, rotations),
+        );
+    }
+}
+
+/// The items of a query, sorted by entity if `sorted`, so their effects, e.g. impulses pushed onto a body,
+/// add up in the same order regardless of how the entities are stored.
+fn ordered<T>(items: impl Iterator<Item = (Entity, T)>, sorted: bool) -> Vec<T> {
+    let mut items: Vec<_> = items.collect();
+    if sorted {
+        items.sort_unstable_by_key(|(entity, _)| *entity);
+    }
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Pushes the impulses computed from the attachments and predicted rotations of a joint's bodies onto them.
+fn solve_joint(
+    bodies: &mut JointBodies,
+    entities: (Entity, Entity),
+    anchors: (Vec3, Vec3),
+    solve: impl FnOnce((&Attachment, &Attachment), (Quat, Quat)) -> JointImpulses,
+) {
+    let Ok([(past_0, mut linear_0, mut angular_0), (past_1, mut linear_1, mut angular_1)]) =
+        bodies.get_many_mut([entities.0, entities.1])
+    else {
+        return;
+    };
 
-            debug_contact(&mut gizmos, contact, &parameters);
+    let attachment_0 = attachment(past_0, linear_0.as_deref(), angular_0.as_deref(), anchors.0);
+    let attachment_1 = attachment(past_1, linear_1.as_deref(), angular_1.as_deref(), anchors.1);
+    let (_, rotation_0) = pose(past_0, linear_0.as_deref(), angular_0.as_deref());
+    let (_, rotation_1) = pose(past_1, linear_1.as_deref(), angular_1.as_deref());
+
+    let impulses = solve((&attachment_0, &attachment_1), (rotation_0, rotation_1));
+
+    if let Some(impulse) = impulses.impulse {
+        if let Some(linear) = &mut linear_0 {
+            linear.push_impulse(impulse);
+        }
+        if let Some(linear) = &mut linear_1 {
+            linear.push_impulse(-impulse);
+        }
+        if let Some(angular) = &mut angular_0 {
+            angular.push_impulse(attachment_0.point, attachment_0.center_of_mass, impulse);
+        }
+        if let Some(angular) = &mut angular_1 {
+            angular.push_impulse(attachment_1.point, attachment_1.center_of_mass, -impulse);
         }
     }
+    for &angular_impulse in &impulses.angular_impulses {
+        if let Some(angular) = &mut angular_0 {
+            angular.push_angular_impulse(angular_impulse);
+        }
+        if let Some(angular) = &mut angular_1 {
+            angular.push_angular_impulse(-angular_impulse);
+        }
+    }
+}
+
+/// The predicted pose of a body, or its current pose if it does not move.
+fn pose(past: &Transform, linear: Option<&Linear>, angular: Option<&Angular>) -> (Vec3, Quat) {
+    (
+        linear.map_or(past.translation, |linear| linear.translation),
+        angular.map_or(past.rotation, |angular| angular.rotation),
+    )
+}
+
+fn attachment(
+    past: &Transform,
+    linear: Option<&Linear>,
+    angular: Option<&Angular>,
+    anchor: Vec3,
+) -> Attachment {
+    let (translation, rotation) = pose(past, linear, angular);
+    Attachment {
+        point: translation + rotation * anchor,
+        past_point: past.translation + past.rotation * anchor,
+        center_of_mass: translation,
+        inv_mass: linear.map_or(Vec3::ZERO, Linear::inv_mass),
+        inv_moment_of_inertia: angular.map_or(Mat3::ZERO, Angular::world_inv_inertia),
+    }
 }
 
 fn derive_translation(
@@ -188,7 +1279,7 @@ fn derive_translation(
     parameters: Res<PhysicsParameters>,
     internal: Res<InternalParameters>,
 ) {
-    let dt = 1.0 / parameters.frequency / internal.substeps as f32 / parameters.time_scale;
+    let dt = internal.dt(&parameters);
 
     for (mut linear, mut transform) in query.iter_mut() {
         linear.apply_impulses();
@@ -202,7 +1293,7 @@ fn derive_rotation(
     parameters: Res<PhysicsParameters>,
     internal: Res<InternalParameters>,
 ) {
-    let dt = 1.0 / parameters.frequency / internal.substeps as f32 / parameters.time_scale;
+    let dt = internal.dt(&parameters);
 
     for (mut angular, mut transform) in query.iter_mut() {
         angular.apply_impulses();
@@ -211,6 +1302,47 @@ fn derive_rotation(
     }
 }
 
+fn collision_events(
+    collisions: Res<Collisions>,
+    mut enter: EventWriter<TriggerEnter>,
+    mut exit: EventWriter<TriggerExit>,
+    mut started: EventWriter<CollisionStarted>,
+    mut ended: EventWriter<CollisionEnded>,
+) {
+    for collision in collisions.started() {
+        let (entity_0, entity_1) = collision.entities;
+        if collision.sensor {
+            enter.send(TriggerEnter(entity_0, entity_1));
+        } else {
+            started.send(CollisionStarted(entity_0, entity_1));
+        }
+    }
+    for collision in collisions.ended() {
+        let (entity_0, entity_1) = collision.entities;
+        if collision.sensor {
+            exit.send(TriggerExit(entity_0, entity_1));
+        } else {
+            ended.send(CollisionEnded(entity_0, entity_1));
+        }
+    }
+}
+
+fn clear_external_forces(
+    mut forces: Query<&mut ExternalForce>,
+    mut torques: Query<&mut ExternalTorque>,
+) {
+    for mut force in forces.iter_mut() {
+        if !force.persistent {
+            force.force = Vec3::ZERO;
+        }
+    }
+    for mut torque in torques.iter_mut() {
+        if !torque.persistent {
+            torque.torque = Vec3::ZERO;
+        }
+    }
+}
+
 fn debug_bodies(query: Query<(&Transform, Option<&Linear>)>, mut gizmos: Gizmos) {
     for (transform, linear) in query.iter() {
         if let Some(linear) = linear {
@@ -229,11 +1361,183 @@ fn debug_bodies(query: Query<(&Transform, Option<&Linear>)>, mut gizmos: Gizmos)
     }
 }
 
-fn debug_contact(gizmos: &mut Gizmos, contact: Contact, parameters: &Res<PhysicsParameters>) {
+fn debug_vehicles(query: Query<(&Vehicle, &Transform)>, mut gizmos: Gizmos) {
+    for (vehicle, transform) in query.iter() {
+        for wheel in &vehicle.wheels {
+            let (anchor, center, axle) = wheel.pose(transform);
+            gizmos.line(anchor, center, Color::GRAY);
+            gizmos.circle(center, axle, wheel.radius, Color::WHITE);
+        }
+    }
+}
+
+fn debug_joints(
+    bodies: Query<&Transform, With<Collider>>,
+    springs: Query<&SpringJoint>,
+    sphericals: Query<&SphericalJoint>,
+    hinges: Query<&HingeJoint>,
+    mut gizmos: Gizmos,
+) {
+    const L: f32 = 0.3;
+
+    // The swing cone is drawn around the first axis, the second axis turns red outside of it.
+    for joint in sphericals.iter() {
+        let Ok([transform_0, transform_1]) = bodies.get_many([joint.bodies.0, joint.bodies.1])
+        else {
+            continue;
+        };
+        let (anchor, _) =
+            debug_anchors(&mut gizmos, (transform_0, transform_1), joint.anchors, true);
+        let axes = (
+            (transform_0.rotation * joint.axes.0).normalize(),
+            (transform_1.rotation * joint.axes.1).normalize(),
+        );
+
+        let limit = joint.swing_limit.min(PI);
+        let center = anchor + L * math::cos(limit) * axes.0;
+        let radius = L * math::sin(limit);
+        let side = axes.0.any_orthonormal_vector();
+        gizmos.circle(center, axes.0, radius, Color::CYAN);
+        for i in 0..4 {
+            let rim = Quat::from_axis_angle(axes.0, i as f32 * FRAC_PI_2) * side;
+            gizmos.line(anchor, center + radius * rim, Color::CYAN);
+        }
+        let swing = axes.0.angle_between(axes.1);
+        let color = if swing > joint.swing_limit {
+            Color::RED
+        } else {
+            Color::WHITE
+        };
+        gizmos.ray(anchor, L * axes.1, color);
+
+        // The twist range is drawn around the bisector of the axes, like the hinge range.
+        let Some(twist_axis) = (axes.0 + axes.1).try_normalize() else {
+            continue;
+        };
+        let normals = (
+            transform_0.rotation * joint.normals.0,
+            transform_1.rotation * joint.normals.1,
+        );
+        let twist = angle_around(twist_axis, normals.0, normals.1);
+        debug_arc(
+            &mut gizmos,
+            anchor,
+            twist_axis,
+            normals,
+            (-joint.twist_limit, joint.twist_limit),
+            twist.abs() > joint.twist_limit,
+        );
+    }
+
+    // The allowed range of the hinge angle is drawn as an arc, the second normal turns red outside of it.
+    for joint in hinges.iter() {
+        let Ok([transform_0, transform_1]) = bodies.get_many([joint.bodies.0, joint.bodies.1])
+        else {
+            continue;
+        };
+        let (anchor, _) =
+            debug_anchors(&mut gizmos, (transform_0, transform_1), joint.anchors, true);
+        let axis = (transform_0.rotation * joint.axes.0).normalize();
+        let normals = (
+            transform_0.rotation * joint.normals.0,
+            transform_1.rotation * joint.normals.1,
+        );
+
+        gizmos.ray(anchor, L * axis, Color::GRAY);
+        let angle = angle_around(axis, normals.0, normals.1);
+        debug_arc(
+            &mut gizmos,
+            anchor,
+            axis,
+            normals,
+            joint.limits,
+            angle < joint.limits.0 || angle > joint.limits.1,
+        );
+    }
+
+    for spring in springs.iter() {
+        let Ok([transform_0, transform_1]) = bodies.get_many([spring.bodies.0, spring.bodies.1])
+        else {
+            continue;
+        };
+        let (anchor_0, anchor_1) = debug_anchors(
+            &mut gizmos,
+            (transform_0, transform_1),
+            spring.anchors,
+            false,
+        );
+
+        // The rest length is drawn from the first anchor, the remaining error up to the second anchor:
+        // red when stretched and blue when compressed.
+        let axis = (anchor_1 - anchor_0).normalize_or_zero();
+        let rest = anchor_0 + spring.rest_length * axis;
+        let error = anchor_0.distance(anchor_1) - spring.rest_length;
+        if error > 0.0 {
+            gizmos.line(anchor_0, rest, Color::CYAN);
+            gizmos.line(rest, anchor_1, Color::RED);
+        } else {
+            gizmos.line(anchor_0, anchor_1, Color::CYAN);
+            gizmos.line(anchor_1, rest, Color::BLUE);
+        }
+    }
+}
+
+/// Draws the anchors of a joint connected to the centers of their bodies and returns them.
+/// If the anchors should coincide, their separation is drawn as the positional error.
+fn debug_anchors(
+    gizmos: &mut Gizmos,
+    transforms: (&Transform, &Transform),
+    anchors: (Vec3, Vec3),
+    coincide: bool,
+) -> (Vec3, Vec3) {
+    let anchors = (
+        transforms.0.transform_point(anchors.0),
+        transforms.1.transform_point(anchors.1),
+    );
+    debug_point(gizmos, anchors.0, Color::CYAN);
+    debug_point(gizmos, anchors.1, Color::CYAN);
+    gizmos.line(transforms.0.translation, anchors.0, Color::GRAY);
+    gizmos.line(transforms.1.translation, anchors.1, Color::GRAY);
+    if coincide {
+        gizmos.line(anchors.0, anchors.1, Color::RED);
+    }
+    anchors
+}
+
+/// Draws the allowed range of the angle around an axis from the first normal as an arc,
+/// and the second normal, red if `violated`.
+fn debug_arc(
+    gizmos: &mut Gizmos,
+    center: Vec3,
+    axis: Vec3,
+    normals: (Vec3, Vec3),
+    limits: (f32, f32),
+    violated: bool,
+) {
+    const L: f32 = 0.3;
+
+    let start = normals.0.reject_from_normalized(axis).normalize_or_zero();
+    let (min, max) = (limits.0.max(-PI), limits.1.min(PI));
+    gizmos.linestrip(
+        (0..=16).map(|i| {
+            let angle = min + (max - min) * i as f32 / 16.0;
+            center + L * (Quat::from_axis_angle(axis, angle) * start)
+        }),
+        Color::CYAN,
+    );
+    let color = if violated { Color::RED } else { Color::WHITE };
+    gizmos.ray(
+        center,
+        L * normals.1.reject_from_normalized(axis).normalize_or_zero(),
+        color,
+    );
+}
+
+fn debug_contact(gizmos: &mut Gizmos, points: (Vec3, Vec3), parameters: &Res<PhysicsParameters>) {
     if parameters.debug {
-        debug_point(gizmos, contact.points.0, Color::YELLOW);
-        debug_point(gizmos, contact.points.1, Color::YELLOW);
-        gizmos.line(contact.points.0, contact.points.1, Color::YELLOW);
+        debug_point(gizmos, points.0, Color::YELLOW);
+        debug_point(gizmos, points.1, Color::YELLOW);
+        gizmos.line(points.0, points.1, Color::YELLOW);
     }
 }
 
@@ -246,3 +1550,471 @@ fn debug_point(gizmos: &mut Gizmos, p: Vec3, color: Color) {
     gizmos.line(p + L * Vec3::Z, p, color);
     gizmos.line(p - L * Vec3::Z, p, color);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+
+    use bevy::{ecs::world::EntityWorldMut, gizmos::GizmoPlugin};
+
+    use super::*;
+    use crate::physics::{
+        field::{Falloff, FieldModel},
+        motion::Rigid,
+    };
+
+    /// Moves the entities it is added to into separate storage, changing the order queries visit them in.
+    #[derive(Component)]
+    struct Marker;
+
+    /// An app running the physics plugin without a renderer.
+    pub(super) fn app(parameters: PhysicsParameters) -> App {
+        // Gizmos need the storage of the gizmo plugin, which in turn needs the shader assets, but no renderer.
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Shader>()
+            .add_plugins((GizmoPlugin, PhysicsPlugin::default()))
+            .insert_resource(parameters);
+        app
+    }
+
+    /// Spawns a static ground plane through the origin.
+    pub(super) fn ground(app: &mut App) -> Entity {
+        app.world
+            .spawn((
+                Collider {
+                    mass: f32::INFINITY,
+                    shape: Shape::Plane { normal: Vec3::Y },
+                },
+                RigidBody::Static,
+                TransformBundle::IDENTITY,
+            ))
+            .id()
+    }
+
+    /// Spawns a dynamic ball of unit mass and a radius of 0.5 at rest.
+    pub(super) fn ball(app: &mut App, translation: Vec3) -> EntityWorldMut<'_> {
+        app.world.spawn((
+            Collider {
+                mass: 1.0,
+                shape: Shape::Ball { radius: 0.5 },
+            },
+            Rigid::default(),
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+        ))
+    }
+
+    /// Simulates a chain of jointed boxes and balls falling onto the ground and hashes the final states of all bodies.
+    fn simulate(steps: usize, marked: bool) -> u64 {
+        let mut app = app(PhysicsParameters {
+            deterministic: true,
+            ..default()
+        });
+
+        ground(&mut app);
+
+        let bodies: Vec<Entity> = (0..12)
+            .map(|i| {
+                let shape = if i % 2 == 0 {
+                    Shape::Cuboid {
+                        half_extents: Vec3::splat(0.4),
+                    }
+                } else {
+                    Shape::Ball { radius: 0.4 }
+                };
+                let transform =
+                    Transform::from_xyz(0.3 * (i % 3) as f32, 0.5 + 0.9 * i as f32, 0.0)
+                        .with_rotation(Quat::from_rotation_z(0.1 * i as f32));
+                let mut body = app.world.spawn((
+                    Collider { mass: 1.0, shape },
+                    Rigid::default(),
+                    TransformBundle::from_transform(transform),
+                ));
+                if marked && i % 3 == 0 {
+                    body.insert(Marker);
+                }
+                body.id()
+            })
+            .collect();
+
+        // A chain of joints, so the impulses of several joints and contacts add up on the same bodies.
+        for (i, pair) in bodies.windows(2).enumerate() {
+            let mut joint = app.world.spawn(
+                SphericalJoint::new(pair[0], pair[1])
+                    .anchors((0.45 * Vec3::Y, -0.45 * Vec3::Y))
+                    .swing_limit(FRAC_PI_2),
+            );
+            if marked && i % 2 == 0 {
+                joint.insert(Marker);
+            }
+        }
+
+        // Overlapping fields, whose accelerations add up on the same bodies.
+        let models = [
+            FieldModel::Directional {
+                direction: Vec3::new(0.3, 0.1, 0.7),
+            },
+            FieldModel::Radial {
+                strength: -2.3,
+                falloff: Falloff::Linear { radius: 20.0 },
+            },
+        ];
+        for (i, model) in models.into_iter().enumerate() {
+            let mut field = app.world.spawn((
+                ForceField {
+                    model,
+                    mode: FieldMode::Acceleration,
+                },
+                Sensor,
+                Collider {
+                    mass: f32::INFINITY,
+                    shape: Shape::Ball { radius: 20.0 },
+                },
+                TransformBundle::from_transform(Transform::from_xyz(1.1, 0.0, 0.3)),
+            ));
+            if marked && i == 0 {
+                field.insert(Marker);
+            }
+        }
+
+        for _ in 0..steps {
+            app.update();
+        }
+
+        let mut states: Vec<_> = app
+            .world
+            .query::<(Entity, &Transform, &Linear, &Angular)>()
+            .iter(&app.world)
+            .collect();
+        states.sort_unstable_by_key(|(entity, ..)| *entity);
+
+        let mut hasher = DefaultHasher::new();
+        for (entity, transform, linear, angular) in states {
+            entity.hash(&mut hasher);
+            let values = transform
+                .translation
+                .to_array()
+                .into_iter()
+                .chain(transform.rotation.to_array())
+                .chain(linear.velocity.to_array())
+                .chain(angular.angular_velocity.to_array());
+            for value in values {
+                value.to_bits().hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn body_damping_overrides_the_global_damping() {
+        let mut app = app(PhysicsParameters {
+            gravity: Vec3::ZERO,
+            linear_damping: 1.0,
+            ..default()
+        });
+        let damped = ball(&mut app, -2.0 * Vec3::X).id();
+        let undamped = ball(&mut app, 2.0 * Vec3::X)
+            .insert(LinearDamping(0.0))
+            .id();
+        for body in [damped, undamped] {
+            app.world.get_mut::<Linear>(body).unwrap().velocity = Vec3::Z;
+        }
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let velocity = |entity| app.world.get::<Linear>(entity).unwrap().velocity.z;
+        assert!(velocity(damped) < 1.0);
+        assert!((velocity(undamped) - 1.0).abs() < 1e-4);
+    }
+
+    /// Slides an upright box along the ground at 2 m/s for a second and returns its final translation and velocity.
+    fn slide(friction: f32) -> (Vec3, Vec3) {
+        let mut app = app(PhysicsParameters {
+            friction,
+            ..default()
+        });
+        ground(&mut app);
+        let body = app
+            .world
+            .spawn((
+                Collider {
+                    mass: 1.0,
+                    shape: Shape::Cuboid {
+                        half_extents: Vec3::splat(0.5),
+                    },
+                },
+                Linear::default().velocity(2.0 * Vec3::X),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.5, 0.0)),
+            ))
+            .id();
+        for _ in 0..60 {
+            app.update();
+        }
+        let translation = app.world.get::<Transform>(body).unwrap().translation;
+        (translation, app.world.get::<Linear>(body).unwrap().velocity)
+    }
+
+    #[test]
+    fn friction_stops_sliding_bodies() {
+        // Decelerating at 0.5 * 10 m/s², the box stops after 0.4 s and 0.4 m.
+        let (translation, velocity) = slide(0.5);
+        assert!(velocity.length() < 1e-3);
+        assert!((translation.x - 0.4).abs() < 0.05);
+
+        let (translation, velocity) = slide(0.0);
+        assert!((velocity.x - 2.0).abs() < 1e-3);
+        assert!((translation.x - 2.0).abs() < 0.01);
+    }
+
+    /// Spawns a kinematic platform whose top is at the origin.
+    fn platform(app: &mut App) -> Entity {
+        app.world
+            .spawn((
+                Collider {
+                    mass: 10.0,
+                    shape: Shape::Cuboid {
+                        half_extents: Vec3::new(2.0, 0.25, 2.0),
+                    },
+                },
+                Rigid::default(),
+                RigidBody::Kinematic,
+                TransformBundle::from_transform(Transform::from_xyz(0.0, -0.25, 0.0)),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn kinematic_platforms_carry_riders_along() {
+        let mut app = app(PhysicsParameters::default());
+        let platform = platform(&mut app);
+        let rider = app
+            .world
+            .spawn((
+                Collider {
+                    mass: 1.0,
+                    shape: Shape::Cuboid {
+                        half_extents: Vec3::splat(0.25),
+                    },
+                },
+                Linear::default(),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.25, 0.0)),
+            ))
+            .id();
+        for _ in 0..10 {
+            app.update();
+        }
+
+        // The platform moves sideways at 1 m/s for a second.
+        for _ in 0..60 {
+            app.world
+                .get_mut::<Transform>(platform)
+                .unwrap()
+                .translation
+                .x += 1.0 / 60.0;
+            app.update();
+        }
+
+        let translation = app.world.get::<Transform>(rider).unwrap().translation;
+        // Friction accelerates the rider at 5 m/s², so it slips by 0.1 until it moves along.
+        assert!((translation.x - 0.9).abs() < 0.05);
+        assert!((translation.y - 0.25).abs() < 0.01);
+        assert!((app.world.get::<Linear>(rider).unwrap().velocity.x - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn kinematic_bodies_are_not_pushed_back() {
+        let mut app = app(PhysicsParameters::default());
+        let platform = platform(&mut app);
+        let heavy = ball(&mut app, Vec3::new(0.0, 0.6, 0.0)).id();
+        app.world.entity_mut(heavy).insert(Collider {
+            mass: 1000.0,
+            shape: Shape::Ball { radius: 0.5 },
+        });
+        app.world.get_mut::<Linear>(heavy).unwrap().velocity = Vec3::new(3.0, -20.0, 0.0);
+        for _ in 0..30 {
+            app.update();
+        }
+
+        let transform = app.world.get::<Transform>(platform).unwrap();
+        assert_eq!(transform.translation, Vec3::new(0.0, -0.25, 0.0));
+        assert_eq!(transform.rotation, Quat::IDENTITY);
+        let linear = app.world.get::<Linear>(platform).unwrap();
+        assert_eq!(linear.velocity, Vec3::ZERO);
+        assert!(app.world.get::<Transform>(heavy).unwrap().translation.y > 0.45);
+    }
+
+    #[test]
+    fn rigid_bodies_are_inferred_until_set_explicitly() {
+        let mut app = app(PhysicsParameters::default());
+        let ball = || Collider {
+            mass: 1.0,
+            shape: Shape::Ball { radius: 0.5 },
+        };
+        let transform = |x: f32| TransformBundle::from_transform(Transform::from_xyz(x, 0.0, 0.0));
+        let dynamic = app
+            .world
+            .spawn((ball(), Rigid::default(), transform(0.0)))
+            .id();
+        let heavy = app
+            .world
+            .spawn((
+                Collider {
+                    mass: f32::INFINITY,
+                    ..ball()
+                },
+                Rigid::default(),
+                transform(2.0),
+            ))
+            .id();
+        let still = app.world.spawn((ball(), transform(4.0))).id();
+        let kinematic = app
+            .world
+            .spawn((ball(), RigidBody::Kinematic, transform(6.0)))
+            .id();
+        app.update();
+
+        let kind = |app: &App, entity| *app.world.get::<RigidBody>(entity).unwrap();
+        assert_eq!(kind(&app, dynamic), RigidBody::Dynamic);
+        assert_eq!(kind(&app, heavy), RigidBody::Static);
+        assert_eq!(kind(&app, still), RigidBody::Static);
+        assert_eq!(kind(&app, kinematic), RigidBody::Kinematic);
+
+        // Inferred kinds follow the motion, explicit ones stay.
+        app.world.entity_mut(still).insert(Rigid::default());
+        app.world.entity_mut(dynamic).insert(RigidBody::Static);
+        app.world.entity_mut(kinematic).insert(Rigid::default());
+        app.update();
+        app.update();
+
+        assert_eq!(kind(&app, still), RigidBody::Dynamic);
+        assert_eq!(kind(&app, dynamic), RigidBody::Static);
+        assert_eq!(kind(&app, kinematic), RigidBody::Kinematic);
+    }
+
+    #[test]
+    fn bodies_with_locked_tilt_never_tip() {
+        let mut app = app(PhysicsParameters::default());
+        ground(&mut app);
+        let transform =
+            Transform::from_xyz(0.0, 1.0, 0.0).with_rotation(Quat::from_rotation_y(0.6));
+        // A blow to a corner, which would tip the box over.
+        let mut impulse = ExternalImpulse::default();
+        impulse.apply_at_point(
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(0.2, 1.5, 0.8),
+            transform.translation,
+        );
+        let body = app
+            .world
+            .spawn((
+                Collider {
+                    mass: 1.0,
+                    shape: Shape::Cuboid {
+                        half_extents: Vec3::new(0.2, 0.5, 0.8),
+                    },
+                },
+                Rigid::default(),
+                LockedAxes::default().rotation(BVec3::new(true, false, true)),
+                impulse,
+                TransformBundle::from_transform(transform),
+            ))
+            .id();
+
+        for _ in 0..60 {
+            app.update();
+            let up = app.world.get::<Transform>(body).unwrap().rotation * Vec3::Y;
+            assert!(up.abs_diff_eq(Vec3::Y, 1e-4));
+        }
+    }
+
+    /// Shoots a small ball with [`Ccd`] at the ground and returns its lowest height.
+    fn shoot_at(ground: Shape) -> f32 {
+        let mut app = app(PhysicsParameters::default());
+        app.world.spawn((
+            Collider {
+                mass: f32::INFINITY,
+                shape: ground,
+            },
+            TransformBundle::IDENTITY,
+        ));
+        let ball = app
+            .world
+            .spawn((
+                Collider {
+                    mass: 1.0,
+                    shape: Shape::Ball { radius: 0.1 },
+                },
+                Linear::default().velocity(Vec3::new(10.0, -100.0, 0.0)),
+                Angular::default(),
+                Ccd,
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 1.0, 0.0)),
+            ))
+            .id();
+
+        (0..30)
+            .map(|_| {
+                app.update();
+                app.world.get::<Transform>(ball).unwrap().translation.y
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn fast_bodies_do_not_tunnel_through_planes() {
+        assert!(shoot_at(Shape::Plane { normal: Vec3::Y }) > 0.0);
+    }
+
+    #[test]
+    fn fast_bodies_do_not_tunnel_through_thin_colliders() {
+        let floor = Shape::Cuboid {
+            half_extents: Vec3::new(5.0, 0.01, 5.0),
+        };
+        assert!(shoot_at(floor) > 0.0);
+    }
+
+    #[test]
+    fn speculative_contacts_stop_fast_approaches() {
+        let mut app = app(PhysicsParameters {
+            gravity: Vec3::ZERO,
+            ..default()
+        });
+        app.world.spawn((
+            Collider {
+                mass: f32::INFINITY,
+                shape: Shape::Cuboid {
+                    half_extents: Vec3::new(5.0, 0.002, 5.0),
+                },
+            },
+            RigidBody::Static,
+            TransformBundle::from_transform(Transform::from_xyz(0.0, -0.002, 0.0)),
+        ));
+        // Within a substep, the ball moves 0.04 from 0.015 above the plate to below it, missing it entirely.
+        let ball = app
+            .world
+            .spawn((
+                Collider {
+                    mass: 1.0,
+                    shape: Shape::Ball { radius: 0.01 },
+                },
+                Linear::default().velocity(-24.0 * Vec3::Y),
+                Angular::default(),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.025, 0.0)),
+            ))
+            .id();
+        app.update();
+
+        assert!(app.world.get::<Transform>(ball).unwrap().translation.y > 0.0);
+        assert!(app.world.get::<Linear>(ball).unwrap().velocity.y > -1e-3);
+    }
+
+    #[test]
+    fn deterministic_mode_reproduces_body_states() {
+        let hash = simulate(120, false);
+        assert_eq!(hash, simulate(120, false));
+        assert_eq!(hash, simulate(120, true));
+    }
+}
@@ -3,8 +3,10 @@ use std::ops::Add;
 use bevy::prelude::*;
 use derive_setters::Setters;
 
+use super::{collider::Collider, math};
+
 /// A rigid body with linear motion.
-// TODO: Add external and internal force
+// TODO: Add internal force
 #[derive(Debug, Component, Clone, Copy, Setters)]
 #[setters(generate_private = false)]
 pub struct Linear {
@@ -12,10 +14,11 @@ pub struct Linear {
     pub(super) translation: Vec3,
     impulse: (Vec3, usize),
     inv_mass: f32,
+    locked: BVec3,
 }
 
 /// A rigid body with angular motion.
-// TODO: Add external and internal torque
+// TODO: Add internal torque
 #[derive(Debug, Component, Clone, Copy, Setters)]
 #[setters(generate_private = false)]
 pub struct Angular {
@@ -23,8 +26,52 @@ pub struct Angular {
     pub(super) rotation: Quat,
     angular_impulse: (Vec3, usize),
     inv_moment_of_inertia: Vec3,
+    locked: BVec3,
+}
+
+/// How a body is moved.
+///
+/// Colliders spawned without one get it inferred: dynamic if they have [`Linear`] motion and a finite mass,
+/// static otherwise. The inferred kind follows later changes to the motion or mass,
+/// until a different kind is set explicitly.
+#[derive(Debug, Component, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RigidBody {
+    /// Moved by forces, impulses, contacts and joints. Requires [`Linear`] and [`Angular`] motion.
+    #[default]
+    Dynamic,
+    /// Never moves. Any [`Linear`] or [`Angular`] motion is ignored.
+    Static,
+    /// Moved only by setting its [`Transform`], from which its velocity is inferred.
+    /// It pushes dynamic bodies and carries them along through friction, but is never pushed back.
+    /// Requires [`Linear`] and [`Angular`] motion.
+    Kinematic,
+}
+
+/// Exponentially decays the linear velocity of a body, overriding the global linear damping.
+#[derive(Debug, Component, Clone, Copy)]
+pub struct LinearDamping(pub f32);
+
+/// Exponentially decays the angular velocity of a body, overriding the global angular damping.
+#[derive(Debug, Component, Clone, Copy)]
+pub struct AngularDamping(pub f32);
+
+/// Freezes individual world axes of the translation and rotation of a body,
+/// e.g. to keep characters upright or a 2.5D game in the XY plane.
+#[derive(Debug, Component, Default, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct LockedAxes {
+    pub translation: BVec3,
+    pub rotation: BVec3,
 }
 
+/// Enables continuous collision detection for a fast dynamic body.
+///
+/// Whenever the body moves further than its collider is thick during a substep,
+/// it is swept from its previous to its predicted pose and clamped to the first impact,
+/// so it can not tunnel through thin colliders.
+#[derive(Debug, Component, Default, Clone, Copy)]
+pub struct Ccd;
+
 /// A rigid body combines linear and angular motion.
 #[derive(Debug, Bundle, Default, Clone, Copy)]
 pub struct Rigid {
@@ -39,6 +86,7 @@ impl Default for Linear {
             translation: Vec3::ZERO,
             impulse: (Vec3::ZERO, 0),
             inv_mass: 0.0,
+            locked: BVec3::FALSE,
         }
     }
 }
@@ -50,17 +98,67 @@ impl Default for Angular {
             rotation: Quat::IDENTITY,
             angular_impulse: (Vec3::ZERO, 0),
             inv_moment_of_inertia: Vec3::ZERO,
+            locked: BVec3::FALSE,
+        }
+    }
+}
+
+impl RigidBody {
+    pub fn is_dynamic(&self) -> bool {
+        *self == RigidBody::Dynamic
+    }
+
+    pub(super) fn inferred(collider: &Collider, linear: bool) -> Self {
+        if linear && collider.inv_mass() > 0.0 {
+            RigidBody::Dynamic
+        } else {
+            RigidBody::Static
         }
     }
 }
 
 impl Linear {
-    pub(super) fn integrate(&mut self, translation: Vec3, force: Vec3, inverse_mass: f32, dt: f32) {
-        self.velocity += dt * force * inverse_mass;
+    /// The acceleration is independent of the mass, whereas the force is scaled by the inverse mass.
+    pub(super) fn integrate(
+        &mut self,
+        translation: Vec3,
+        acceleration: Vec3,
+        force: Vec3,
+        inverse_mass: f32,
+        locked: BVec3,
+        dt: f32,
+    ) {
+        self.locked = locked;
+        self.velocity = unlocked(self.velocity, locked);
+        self.accelerate(acceleration, force, inverse_mass, dt);
         self.translation = translation + dt * self.velocity;
         self.inv_mass = inverse_mass;
     }
 
+    /// Changes the velocity without moving the body.
+    /// Bodies with infinite mass are not accelerated at all.
+    pub(super) fn accelerate(
+        &mut self,
+        acceleration: Vec3,
+        force: Vec3,
+        inverse_mass: f32,
+        dt: f32,
+    ) {
+        if inverse_mass > 0.0 {
+            self.velocity += unlocked(dt * (acceleration + force * inverse_mass), self.locked);
+        }
+    }
+
+    /// Decays the velocity by the given rate per second, independently of the step size.
+    pub(super) fn damp(&mut self, damping: f32, dt: f32) {
+        self.velocity *= math::exp(-damping * dt);
+    }
+
+    /// Instantaneously changes the velocity by an impulse acting on the center of mass.
+    pub(super) fn receive_impulse(&mut self, impulse: Vec3, inverse_mass: f32) {
+        self.velocity += unlocked(impulse * inverse_mass, self.locked);
+    }
+
     pub(super) fn push_impulse(&mut self, impulse: Vec3) {
         self.impulse.0 += impulse;
         self.impulse.1 += 1;
@@ -69,7 +167,7 @@ impl Linear {
     pub(super) fn apply_impulses(&mut self) {
         if self.impulse.1 > 0 {
             let total_impulse = self.impulse.0 / self.impulse.1 as f32;
-            self.translation += total_impulse * self.inv_mass;
+            self.translation += unlocked(total_impulse * self.inv_mass, self.locked);
             self.impulse = (Vec3::ZERO, 0);
         }
     }
@@ -77,32 +175,69 @@ impl Linear {
     pub(super) fn derive(&mut self, translation: Vec3, dt: f32) {
         self.velocity = (self.translation - translation) / dt;
     }
+
+    /// The inverse mass along each world axis, zero along locked axes.
+    pub(super) fn inv_mass(&self) -> Vec3 {
+        unlocked(Vec3::splat(self.inv_mass), self.locked)
+    }
 }
 
 impl Angular {
-    pub(super) fn integrate(&mut self, rotation: Quat, inverse_moment_of_inertia: Vec3, dt: f32) {
+    pub(super) fn integrate(
+        &mut self,
+        rotation: Quat,
+        torque: Vec3,
+        inverse_moment_of_inertia: Vec3,
+        locked: BVec3,
+        dt: f32,
+    ) {
+        self.locked = locked;
+        self.angular_velocity = unlocked(self.angular_velocity, locked);
+        self.receive_impulse(rotation, dt * torque, inverse_moment_of_inertia);
         let delta_rotation =
             Quat::from_vec4(dt * 0.5 * self.angular_velocity.extend(0.0)) * self.rotation;
         self.rotation = (rotation + delta_rotation).normalize();
         self.inv_moment_of_inertia = inverse_moment_of_inertia;
     }
 
+    /// Decays the angular velocity by the given rate per second, independently of the step size.
+    pub(super) fn damp(&mut self, damping: f32, dt: f32) {
+        self.angular_velocity *= math::exp(-damping * dt);
+    }
+
+    /// Instantaneously changes the angular velocity by a world-space angular impulse.
+    pub(super) fn receive_impulse(
+        &mut self,
+        rotation: Quat,
+        angular_impulse: Vec3,
+        inverse_moment_of_inertia: Vec3,
+    ) {
+        self.angular_velocity +=
+            world_inv_inertia(rotation, inverse_moment_of_inertia, self.locked) * angular_impulse;
+    }
+
     pub(super) fn push_impulse(
         &mut self,
         point_of_attack: Vec3,
         center_of_mass: Vec3,
         impulse: Vec3,
     ) {
-        self.angular_impulse.0 +=
-            (self.inv_moment_of_inertia * (point_of_attack - center_of_mass)).cross(impulse);
+        self.push_angular_impulse((point_of_attack - center_of_mass).cross(impulse));
+    }
+
+    /// Pushes an impulse which only rotates the body.
+    pub(super) fn push_angular_impulse(&mut self, angular_impulse: Vec3) {
+        self.angular_impulse.0 += self.world_inv_inertia() * angular_impulse;
         self.angular_impulse.1 += 1;
     }
 
     pub(super) fn apply_impulses(&mut self) {
         if self.angular_impulse.1 > 0 {
-            let delta = Quat::from_vec4(
-                0.5 * self.angular_impulse.0.extend(0.0) / self.angular_impulse.1 as f32,
-            ) * self.rotation;
+            let angular_impulse = unlocked(
+                self.angular_impulse.0 / self.angular_impulse.1 as f32,
+                self.locked,
+            );
+            let delta = Quat::from_vec4(0.5 * angular_impulse.extend(0.0)) * self.rotation;
             self.rotation = self.rotation.add(delta).normalize();
             self.angular_impulse = (Vec3::ZERO, 0);
         }
@@ -115,4 +250,100 @@ impl Angular {
         }
         self.angular_velocity = 2.0 * delta.xyz() / dt;
     }
+
+    /// The inverse inertia tensor at the predicted rotation, zero around locked world axes.
+    pub(super) fn world_inv_inertia(&self) -> Mat3 {
+        world_inv_inertia(self.rotation, self.inv_moment_of_inertia, self.locked)
+    }
+}
+
+/// Zeroes the locked components of a vector.
+fn unlocked(vector: Vec3, locked: BVec3) -> Vec3 {
+    Vec3::select(locked, Vec3::ZERO, vector)
+}
+
+/// Rotates the inverse principal moments of inertia into world space, then removes the locked world axes.
+fn world_inv_inertia(rotation: Quat, inverse_moment_of_inertia: Vec3, locked: BVec3) -> Mat3 {
+    let rotation = Mat3::from_quat(rotation);
+    let lock = Mat3::from_diagonal(unlocked(Vec3::ONE, locked));
+    lock * rotation * Mat3::from_diagonal(inverse_moment_of_inertia) * rotation.transpose() * lock
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn gravity_accelerates_bodies_regardless_of_their_mass() {
+        let gravity = Vec3::new(0.0, -10.0, 0.0);
+        let mut light = Linear::default();
+        let mut heavy = Linear::default();
+        light.integrate(Vec3::ZERO, gravity, Vec3::ZERO, 1.0, BVec3::FALSE, 0.1);
+        heavy.integrate(Vec3::ZERO, gravity, Vec3::ZERO, 0.01, BVec3::FALSE, 0.1);
+
+        assert_eq!(light.velocity, Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(heavy.velocity, light.velocity);
+    }
+
+    #[test]
+    fn forces_accelerate_lighter_bodies_more() {
+        let force = Vec3::new(2.0, 0.0, 0.0);
+        let mut light = Linear::default();
+        let mut heavy = Linear::default();
+        let mut immovable = Linear::default();
+        light.integrate(Vec3::ZERO, Vec3::ZERO, force, 1.0, BVec3::FALSE, 0.5);
+        heavy.integrate(Vec3::ZERO, Vec3::ZERO, force, 0.5, BVec3::FALSE, 0.5);
+        immovable.integrate(Vec3::ZERO, Vec3::ZERO, force, 0.0, BVec3::FALSE, 0.5);
+
+        assert_eq!(light.velocity, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(heavy.velocity, Vec3::new(0.5, 0.0, 0.0));
+        assert_eq!(immovable.velocity, Vec3::ZERO);
+    }
+
+    #[test]
+    fn damping_is_independent_of_the_step_size() {
+        let mut coarse = Linear::default().velocity(Vec3::X);
+        let mut fine = coarse;
+        coarse.damp(2.0, 0.1);
+        fine.damp(2.0, 0.05);
+        fine.damp(2.0, 0.05);
+
+        assert!((coarse.velocity.x - (-0.2f32).exp()).abs() < 1e-6);
+        assert!(coarse.velocity.abs_diff_eq(fine.velocity, 1e-6));
+    }
+
+    #[test]
+    fn locks_apply_to_world_axes_of_rotated_bodies() {
+        // Turned a quarter around Y, the body's Z-axis points along world X.
+        let mut angular = Angular::default();
+        angular.integrate(
+            Quat::from_rotation_y(FRAC_PI_2),
+            Vec3::ZERO,
+            Vec3::new(1.0, 2.0, 3.0),
+            BVec3::new(false, false, true),
+            1.0,
+        );
+
+        let expected = Mat3::from_diagonal(Vec3::new(3.0, 2.0, 0.0));
+        assert!(angular.world_inv_inertia().abs_diff_eq(expected, 1e-6));
+    }
+
+    #[test]
+    fn impulses_turn_bodies_around_their_principal_axes() {
+        // Easiest to turn around its own X-axis, which points along world Y.
+        let mut angular = Angular::default();
+        let rotation = Quat::from_rotation_z(FRAC_PI_2);
+        angular.integrate(
+            rotation,
+            Vec3::ZERO,
+            Vec3::new(1.0, 0.25, 0.25),
+            BVec3::FALSE,
+            1.0,
+        );
+        angular.push_impulse(Vec3::Z, Vec3::ZERO, Vec3::X);
+
+        assert!(angular.angular_impulse.0.abs_diff_eq(Vec3::Y, 1e-6));
+    }
 }
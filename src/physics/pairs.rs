@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+/// Emitted once a sensor starts overlapping another collider. The first entity is the sensor.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TriggerEnter(pub Entity, pub Entity);
+
+/// Emitted once a sensor stops overlapping another collider. The first entity is the sensor.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TriggerExit(pub Entity, pub Entity);
+
+/// Emitted once two colliders start touching.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CollisionStarted(pub Entity, pub Entity);
+
+/// Emitted once two colliders stop touching.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CollisionEnded(pub Entity, pub Entity);
+
+/// The contacts found by the narrow phase during the current substep.
+///
+/// Systems in [`SubstepSet::ModifyContacts`](super::SubstepSet::ModifyContacts) may alter them before they are solved.
+/// The order of entities within a pair is arbitrary and may change from substep to substep.
+#[derive(Resource, Debug, Default)]
+pub struct Contacts(Vec<ContactPair>);
+
+#[derive(Debug, Clone, Copy)]
+pub struct ContactPair {
+    pub entities: (Entity, Entity),
+    /// The deepest points of each collider at the start of the substep.
+    pub points: (Vec3, Vec3),
+    /// Points outwards from the first collider towards the second one.
+    pub normal: Vec3,
+    /// The separation along the normal at the start of the substep.
+    /// Negative when penetrating, positive for speculative contacts.
+    pub depth: f32,
+    /// Disabled contacts are neither solved nor reported as collisions.
+    pub enabled: bool,
+    /// The Coulomb friction coefficient.
+    pub friction: f32,
+    /// The velocity at which the second collider is carried along the surface of the first one,
+    /// e.g. for conveyor belts.
+    pub surface_velocity: Vec3,
+    pub(super) sensors: (bool, bool),
+}
+
+impl Contacts {
+    pub fn iter(&self) -> impl Iterator<Item = &ContactPair> {
+        self.0.iter()
+    }
+
+    #[allow(unused)]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut ContactPair> {
+        self.0.iter_mut()
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub(super) fn push(&mut self, pair: ContactPair) {
+        self.0.push(pair);
+    }
+}
+
+impl ContactPair {
+    #[allow(unused)]
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.0 == entity || self.entities.1 == entity
+    }
+
+    pub fn is_sensor(&self) -> bool {
+        self.sensors.0 || self.sensors.1
+    }
+
+    /// Whether the colliders were still apart at the start of the substep, but within the prediction distance.
+    /// Such contacts only push the colliders apart if their motion during the substep closes the gap.
+    pub fn is_speculative(&self) -> bool {
+        self.depth > 0.0
+    }
+
+    /// Swaps the roles of both colliders, inverting the normal and the surface velocity.
+    #[allow(unused)]
+    pub fn flip(&mut self) {
+        self.entities = (self.entities.1, self.entities.0);
+        self.points = (self.points.1, self.points.0);
+        self.sensors = (self.sensors.1, self.sensors.0);
+        self.normal = -self.normal;
+        self.surface_velocity = -self.surface_velocity;
+    }
+}
+
+/// Pairs of colliders which touched during the previous and the current step, ordered by their entities.
+#[derive(Resource, Debug, Default)]
+pub struct Collisions {
+    previous: BTreeMap<(Entity, Entity), Collision>,
+    current: BTreeMap<(Entity, Entity), Collision>,
+}
+
+/// The contact between two colliders during the last substep they touched in.
+#[derive(Debug, Clone, Copy)]
+pub struct Collision {
+    pub entities: (Entity, Entity),
+    pub points: (Vec3, Vec3),
+    pub normals: (Vec3, Vec3),
+    /// The normal impulse summed over all substeps of the step.
+    pub normal_impulse: f32,
+    pub(super) sensor: bool,
+}
+
+impl Collisions {
+    /// All pairs of non-sensor colliders touching during the last step.
+    #[allow(unused)]
+    pub fn iter(&self) -> impl Iterator<Item = &Collision> {
+        self.current.values().filter(|collision| !collision.sensor)
+    }
+
+    pub fn get(&self, entity_0: Entity, entity_1: Entity) -> Option<&Collision> {
+        self.current
+            .get(&key((entity_0, entity_1), (false, false)))
+            .filter(|collision| !collision.sensor)
+    }
+
+    pub(super) fn begin_step(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Records that two colliders touch during the current substep.
+    pub(super) fn insert(
+        &mut self,
+        entities: (Entity, Entity),
+        sensors: (bool, bool),
+        points: (Vec3, Vec3),
+        normals: (Vec3, Vec3),
+        normal_impulse: f32,
+    ) {
+        let key = key(entities, sensors);
+        let swapped = key != entities;
+        let collision = self.current.entry(key).or_insert(Collision {
+            entities: key,
+            points,
+            normals,
+            normal_impulse: 0.0,
+            sensor: sensors.0 || sensors.1,
+        });
+        collision.points = if swapped {
+            (points.1, points.0)
+        } else {
+            points
+        };
+        collision.normals = if swapped {
+            (normals.1, normals.0)
+        } else {
+            normals
+        };
+        collision.normal_impulse += normal_impulse;
+    }
+
+    pub(super) fn started(&self) -> impl Iterator<Item = &Collision> {
+        self.current
+            .iter()
+            .filter(|(key, _)| !self.previous.contains_key(*key))
+            .map(|(_, collision)| collision)
+    }
+
+    pub(super) fn ended(&self) -> impl Iterator<Item = &Collision> {
+        self.previous
+            .iter()
+            .filter(|(key, _)| !self.current.contains_key(*key))
+            .map(|(_, collision)| collision)
+    }
+}
+
+/// Orders the entities of a pair independently of the broad phase, with a sensor coming first.
+fn key(entities: (Entity, Entity), sensors: (bool, bool)) -> (Entity, Entity) {
+    match sensors {
+        (false, true) => (entities.1, entities.0),
+        (true, false) => entities,
+        _ => (entities.0.min(entities.1), entities.0.max(entities.1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{
+        collider::{Collider, Sensor, Shape},
+        motion::{Linear, RigidBody},
+        tests::{app, ball, ground},
+        PhysicsParameters, Substep, SubstepSet,
+    };
+
+    #[derive(Component)]
+    struct Ground;
+
+    #[derive(Resource)]
+    struct Modification(fn(&mut ContactPair));
+
+    /// Runs a contact modification on all contacts with the ground, which is made their first collider.
+    fn modified(modify: fn(&mut ContactPair)) -> Transform {
+        let mut app = app(PhysicsParameters::default());
+        let ground = ground(&mut app);
+        app.world.entity_mut(ground).insert(Ground);
+        let ball = ball(&mut app, 0.5 * Vec3::Y).id();
+        app.insert_resource(Modification(modify)).add_systems(
+            Substep,
+            (|mut contacts: ResMut<Contacts>,
+              ground: Query<(), With<Ground>>,
+              modification: Res<Modification>| {
+                for pair in contacts.iter_mut() {
+                    if ground.contains(pair.entities.1) {
+                        pair.flip();
+                    }
+                    if ground.contains(pair.entities.0) {
+                        (modification.0)(pair);
+                    }
+                }
+            })
+            .in_set(SubstepSet::ModifyContacts),
+        );
+
+        for _ in 0..30 {
+            app.update();
+        }
+        *app.world.get::<Transform>(ball).unwrap()
+    }
+
+    #[test]
+    fn unmodified_contacts_support_bodies() {
+        let transform = modified(|_| {});
+        assert!((transform.translation - 0.5 * Vec3::Y).length() < 0.01);
+    }
+
+    #[test]
+    fn disabled_contacts_are_not_solved() {
+        let transform = modified(|pair| pair.enabled = false);
+        assert!(transform.translation.y < 0.0);
+    }
+
+    #[test]
+    fn overridden_normals_change_the_push_direction() {
+        let transform = modified(|pair| pair.normal = Vec3::new(1.0, 1.0, 0.0).normalize());
+        assert!(transform.translation.x > 0.01);
+    }
+
+    #[test]
+    fn surface_velocities_carry_bodies_along() {
+        let transform = modified(|pair| pair.surface_velocity = Vec3::Z);
+        assert!(transform.translation.z > 0.01);
+    }
+
+    #[test]
+    fn sensors_report_overlaps_without_pushing() {
+        let mut app = app(PhysicsParameters {
+            gravity: Vec3::ZERO,
+            ..default()
+        });
+        let sensor = app
+            .world
+            .spawn((
+                Sensor,
+                Collider {
+                    mass: f32::INFINITY,
+                    shape: Shape::Cuboid {
+                        half_extents: Vec3::splat(0.5),
+                    },
+                },
+                TransformBundle::IDENTITY,
+            ))
+            .id();
+        let ball = ball(&mut app, -2.0 * Vec3::X).id();
+        app.world.get_mut::<Linear>(ball).unwrap().velocity = 6.0 * Vec3::X;
+
+        // Event buffers are only swapped on fixed updates, so read them through readers.
+        let mut enter = app.world.resource::<Events<TriggerEnter>>().get_reader();
+        let mut exit = app.world.resource::<Events<TriggerExit>>().get_reader();
+        let (mut entered, mut exited) = (Vec::new(), Vec::new());
+        for step in 0..60 {
+            app.update();
+            let events = app.world.resource::<Events<TriggerEnter>>();
+            entered.extend(enter.read(events).map(|event| (step, event.0, event.1)));
+            let events = app.world.resource::<Events<TriggerExit>>();
+            exited.extend(exit.read(events).map(|event| (step, event.0, event.1)));
+            assert!(app.world.resource::<Collisions>().iter().next().is_none());
+        }
+
+        assert!(matches!(entered[..], [(_, s, b)] if (s, b) == (sensor, ball)));
+        assert!(matches!(exited[..], [(_, s, b)] if (s, b) == (sensor, ball)));
+        assert!(entered[0].0 < exited[0].0);
+        let linear = app.world.get::<Linear>(ball).unwrap();
+        assert!((linear.velocity - 6.0 * Vec3::X).length() < 1e-4);
+    }
+
+    #[test]
+    fn landing_and_leaving_start_and_end_a_collision() {
+        let mut app = app(PhysicsParameters::default());
+        let ground = ground(&mut app);
+        let ball = ball(&mut app, 0.5 * Vec3::Y).id();
+        let mut started = app
+            .world
+            .resource::<Events<CollisionStarted>>()
+            .get_reader();
+        let mut ended = app.world.resource::<Events<CollisionEnded>>().get_reader();
+
+        for _ in 0..30 {
+            app.update();
+        }
+        let events = app.world.resource::<Events<CollisionStarted>>();
+        let started: Vec<_> = started
+            .read(events)
+            .map(|event| (event.0, event.1))
+            .collect();
+        assert_eq!(started, [(ground, ball)]);
+        let collisions = app.world.resource::<Collisions>();
+        let collision = collisions.get(ball, ground).unwrap();
+        assert_eq!(collision.entities, (ground, ball));
+        assert!(collision.normals.0.abs_diff_eq(Vec3::Y, 1e-3));
+        // Resting on the ground takes the weight of the ball over a step.
+        assert!((collision.normal_impulse - 10.0 / 60.0).abs() < 1e-3);
+
+        app.world.get_mut::<Linear>(ball).unwrap().velocity = 5.0 * Vec3::Y;
+        app.update();
+        let events = app.world.resource::<Events<CollisionEnded>>();
+        let ended: Vec<_> = ended.read(events).map(|event| (event.0, event.1)).collect();
+        assert_eq!(ended, [(ground, ball)]);
+        assert!(app
+            .world
+            .resource::<Collisions>()
+            .get(ground, ball)
+            .is_none());
+    }
+
+    #[test]
+    fn kinematic_bodies_collide_with_static_ones() {
+        let mut app = app(PhysicsParameters::default());
+        let ground = ground(&mut app);
+        let body = ball(&mut app, 0.4 * Vec3::Y)
+            .insert(RigidBody::Kinematic)
+            .id();
+        app.update();
+
+        assert!(app
+            .world
+            .resource::<Collisions>()
+            .get(ground, body)
+            .is_some());
+        let started = app.world.resource::<Events<CollisionStarted>>();
+        assert_eq!(started.len(), 1);
+    }
+
+    #[test]
+    fn hovering_bodies_do_not_collide() {
+        let mut app = app(PhysicsParameters {
+            gravity: Vec3::ZERO,
+            contact_prediction: 0.02,
+            ..default()
+        });
+        ground(&mut app);
+        ball(&mut app, 0.51 * Vec3::Y);
+        for _ in 0..5 {
+            app.update();
+            assert!(app.world.resource::<Collisions>().iter().next().is_none());
+            assert!(app.world.resource::<Events<CollisionStarted>>().is_empty());
+        }
+    }
+}
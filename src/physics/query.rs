@@ -0,0 +1,460 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use parry3d::{
+    bounding_volume::{Aabb, BoundingVolume},
+    query::{time_of_impact, Ray, RayCast, TOIStatus},
+};
+
+use super::{
+    broad_phase::{swept_aabb, BroadPhase},
+    collider::{intersects, project_point, Collider, CollisionLayers, Sensor, Shape},
+    convert,
+    util::Vector,
+};
+
+/// Queries against the colliders known to the [`BroadPhase`].
+#[derive(SystemParam)]
+pub struct SpatialQuery<'w, 's> {
+    colliders: Query<'w, 's, (&'static Collider, &'static Transform, Has<Sensor>)>,
+    broad_phase: Res<'w, BroadPhase>,
+}
+
+/// Restricts which colliders are considered by a [`SpatialQuery`].
+#[derive(Debug, Clone)]
+pub struct QueryFilter {
+    excluded: Vec<Entity>,
+    /// Only colliders which are members of at least one of these layers are considered.
+    mask: u32,
+    sensors: bool,
+}
+
+impl Default for QueryFilter {
+    fn default() -> Self {
+        Self {
+            excluded: Vec::new(),
+            mask: u32::MAX,
+            sensors: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub entity: Entity,
+    /// Distance from the ray origin along the normalized direction.
+    pub distance: f32,
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeHit {
+    pub entity: Entity,
+    /// Distance travelled along the normalized direction until the first impact.
+    pub distance: f32,
+    /// The contact point on the hit collider at the time of impact.
+    pub point: Vec3,
+    /// The outward normal of the hit collider at the contact point.
+    pub normal: Vec3,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[allow(unused)]
+pub struct PointProjection {
+    pub entity: Entity,
+    /// The closest point on the surface of the collider.
+    pub point: Vec3,
+    pub is_inside: bool,
+}
+
+impl QueryFilter {
+    pub fn exclude(mut self, entity: Entity) -> Self {
+        self.excluded.push(entity);
+        self
+    }
+
+    pub fn mask(mut self, mask: u32) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Ignores [`Sensor`] colliders, e.g. when moving through the world.
+    pub fn exclude_sensors(mut self) -> Self {
+        self.sensors = false;
+        self
+    }
+
+    fn accepts(&self, entity: Entity, layers: CollisionLayers) -> bool {
+        layers.memberships & self.mask != 0 && !self.excluded.contains(&entity)
+    }
+}
+
+impl SpatialQuery<'_, '_> {
+    /// Finds the closest collider hit by a ray.
+    /// A ray starting inside a collider hits it at distance zero.
+    pub fn cast_ray(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Option<RayHit> {
+        self.ray_hits(origin, direction, max_toi, filter)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Finds all colliders hit by a ray, sorted by distance.
+    #[allow(unused)]
+    pub fn cast_ray_all(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Vec<RayHit> {
+        let mut hits: Vec<_> = self.ray_hits(origin, direction, max_toi, filter).collect();
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    /// Sweeps a shape along a direction without rotating it and finds the first collider hit.
+    /// Colliders which already overlap the shape are only hit if the shape moves further into them,
+    /// in which case the distance is zero.
+    pub fn cast_shape(
+        &self,
+        shape: &Shape,
+        transform: &Transform,
+        direction: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Option<ShapeHit> {
+        let direction = direction.normalize_or_zero();
+        let bounds = swept_aabb(shape, transform, max_toi * direction);
+        let iso = convert::to_iso(*transform);
+        let velocity = convert::to_vec(direction);
+        let parry_shape = shape.parry_shape();
+
+        self.broad_phase
+            .bounds()
+            .filter(|(entity, aabb, layers)| {
+                filter.accepts(*entity, *layers) && aabb.intersects(&bounds)
+            })
+            .filter_map(|(entity, _, _)| {
+                let (collider, collider_transform) = self.collider(entity, filter)?;
+                let collider_iso = convert::to_iso(*collider_transform);
+                let collider_shape = collider.shape.parry_shape();
+                let toi = time_of_impact(
+                    &iso,
+                    &velocity,
+                    parry_shape.as_ref(),
+                    &collider_iso,
+                    &Vector::zeros(),
+                    collider_shape.as_ref(),
+                    max_toi,
+                    false,
+                )
+                .ok()??;
+
+                // Witnesses are undefined when penetrating, so fall back to the contact at the start pose.
+                let (point, normal) = if toi.status == TOIStatus::Penetrating {
+                    let contact = parry3d::query::contact(
+                        &iso,
+                        parry_shape.as_ref(),
+                        &collider_iso,
+                        collider_shape.as_ref(),
+                        0.0,
+                    )
+                    .ok()??;
+                    (contact.point2, contact.normal2)
+                } else {
+                    (collider_iso * toi.witness2, collider_iso * toi.normal2)
+                };
+
+                Some(ShapeHit {
+                    entity,
+                    distance: toi.toi,
+                    point: convert::point(point),
+                    normal: convert::vec(normal.into_inner()),
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Finds the closest point on the surface of any collider.
+    /// Colliders containing the point take precedence over closer surfaces of other colliders.
+    #[allow(unused)]
+    pub fn project_point(&self, point: Vec3, filter: &QueryFilter) -> Option<PointProjection> {
+        self.candidates(filter, |_| true)
+            .map(|(entity, collider, transform)| {
+                let (projection, is_inside) = project_point(&collider.shape, transform, point);
+                PointProjection {
+                    entity,
+                    point: projection,
+                    is_inside,
+                }
+            })
+            .min_by(|a, b| {
+                b.is_inside.cmp(&a.is_inside).then(
+                    a.point
+                        .distance_squared(point)
+                        .total_cmp(&b.point.distance_squared(point)),
+                )
+            })
+    }
+
+    /// Finds all colliders containing a point.
+    #[allow(unused)]
+    pub fn point_intersections(&self, point: Vec3, filter: &QueryFilter) -> Vec<Entity> {
+        let parry_point = convert::to_point(point);
+        self.candidates(filter, |aabb| aabb.contains_local_point(&parry_point))
+            .filter(|(_, collider, transform)| {
+                collider
+                    .shape
+                    .parry_shape()
+                    .contains_point(&convert::to_iso(**transform), &parry_point)
+            })
+            .map(|(entity, _, _)| entity)
+            .collect()
+    }
+
+    /// Finds all colliders overlapping a shape.
+    pub fn shape_intersections(
+        &self,
+        shape: &Shape,
+        transform: &Transform,
+        filter: &QueryFilter,
+    ) -> Vec<Entity> {
+        let bounds = swept_aabb(shape, transform, Vec3::ZERO);
+        self.candidates(filter, |aabb| aabb.intersects(&bounds))
+            .filter(|(_, collider, collider_transform)| {
+                intersects((shape, &collider.shape), (transform, collider_transform))
+            })
+            .map(|(entity, _, _)| entity)
+            .collect()
+    }
+
+    /// Finds all colliders whose bounds overlap a box.
+    /// The bounds are those of the broad phase, which cover a collider's motion over a whole step.
+    #[allow(unused)]
+    pub fn aabb_intersections(&self, mins: Vec3, maxs: Vec3, filter: &QueryFilter) -> Vec<Entity> {
+        let bounds = Aabb::new(convert::to_point(mins), convert::to_point(maxs));
+        self.candidates(filter, |aabb| aabb.intersects(&bounds))
+            .map(|(entity, _, _)| entity)
+            .collect()
+    }
+
+    /// Colliders accepted by the filter whose bounds pass the given test.
+    fn candidates<'a>(
+        &'a self,
+        filter: &'a QueryFilter,
+        test: impl Fn(&Aabb) -> bool + 'a,
+    ) -> impl Iterator<Item = (Entity, &'a Collider, &'a Transform)> + 'a {
+        self.broad_phase
+            .bounds()
+            .filter(move |(entity, aabb, layers)| filter.accepts(*entity, *layers) && test(aabb))
+            .filter_map(|(entity, _, _)| {
+                let (collider, transform) = self.collider(entity, filter)?;
+                Some((entity, collider, transform))
+            })
+    }
+
+    fn collider(&self, entity: Entity, filter: &QueryFilter) -> Option<(&Collider, &Transform)> {
+        let (collider, transform, sensor) = self.colliders.get(entity).ok()?;
+        (filter.sensors || !sensor).then_some((collider, transform))
+    }
+
+    fn ray_hits<'a>(
+        &'a self,
+        origin: Vec3,
+        direction: Vec3,
+        max_toi: f32,
+        filter: &'a QueryFilter,
+    ) -> impl Iterator<Item = RayHit> + 'a {
+        let direction = direction.normalize_or_zero();
+        let ray = Ray::new(convert::to_point(origin), convert::to_vec(direction));
+
+        self.broad_phase
+            .bounds()
+            .filter(move |(entity, aabb, layers)| {
+                filter.accepts(*entity, *layers) && aabb.intersects_local_ray(&ray, max_toi)
+            })
+            .filter_map(move |(entity, _, _)| {
+                let (collider, transform) = self.collider(entity, filter)?;
+                let intersection = collider.shape.parry_shape().cast_ray_and_get_normal(
+                    &convert::to_iso(*transform),
+                    &ray,
+                    max_toi,
+                    true,
+                )?;
+                Some(RayHit {
+                    entity,
+                    distance: intersection.toi,
+                    point: origin + intersection.toi * direction,
+                    normal: convert::vec(intersection.normal),
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::physics::{collider::Shape, tests::app, PhysicsParameters};
+
+    /// A box at the origin, and balls three and six units further along the X-axis, the far one a sensor on
+    /// another layer.
+    fn scene() -> (App, [Entity; 3]) {
+        let mut app = app(PhysicsParameters::default());
+        let mut spawn = |shape: Shape, x: f32| {
+            app.world
+                .spawn((
+                    Collider {
+                        mass: f32::INFINITY,
+                        shape,
+                    },
+                    TransformBundle::from_transform(Transform::from_xyz(x, 0.0, 0.0)),
+                ))
+                .id()
+        };
+        let cuboid = spawn(
+            Shape::Cuboid {
+                half_extents: Vec3::splat(0.5),
+            },
+            0.0,
+        );
+        let ball = spawn(Shape::Ball { radius: 0.5 }, 3.0);
+        let far = spawn(Shape::Ball { radius: 0.5 }, 6.0);
+        app.world
+            .entity_mut(far)
+            .insert((Sensor, CollisionLayers::new(0b10, u32::MAX)));
+        app.update();
+        (app, [cuboid, ball, far])
+    }
+
+    #[test]
+    fn rays_hit_the_closest_collider() {
+        let (mut app, [cuboid, ball, far]) = scene();
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let filter = QueryFilter::default();
+
+        let hit = query
+            .cast_ray(-5.0 * Vec3::X, Vec3::X, 100.0, &filter)
+            .unwrap();
+        assert_eq!(hit.entity, cuboid);
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+        assert!(hit.normal.abs_diff_eq(-Vec3::X, 1e-5));
+
+        let hits = query.cast_ray_all(-5.0 * Vec3::X, Vec3::X, 100.0, &filter);
+        let entities: Vec<_> = hits.iter().map(|hit| hit.entity).collect();
+        assert_eq!(entities, [cuboid, ball, far]);
+
+        let inside = query.cast_ray(Vec3::ZERO, Vec3::X, 100.0, &filter).unwrap();
+        assert_eq!((inside.entity, inside.distance), (cuboid, 0.0));
+        assert!(query
+            .cast_ray(-5.0 * Vec3::X, Vec3::Y, 100.0, &filter)
+            .is_none());
+        assert!(query
+            .cast_ray(-5.0 * Vec3::X, Vec3::X, 4.0, &filter)
+            .is_none());
+    }
+
+    #[test]
+    fn shapes_cast_until_their_first_impact() {
+        let (mut app, [cuboid, ..]) = scene();
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let filter = QueryFilter::default();
+        let shape = Shape::Ball { radius: 0.25 };
+        let start = Transform::from_xyz(-5.0, 0.0, 0.0);
+
+        let hit = query
+            .cast_shape(&shape, &start, Vec3::X, 100.0, &filter)
+            .unwrap();
+        assert_eq!(hit.entity, cuboid);
+        assert!((hit.distance - 4.25).abs() < 1e-4);
+        assert!(hit.point.abs_diff_eq(Vec3::new(-0.5, 0.0, 0.0), 1e-3));
+        assert!(hit.normal.abs_diff_eq(-Vec3::X, 1e-3));
+
+        assert!(query
+            .cast_shape(&shape, &start, -Vec3::X, 100.0, &filter)
+            .is_none());
+        assert!(query
+            .cast_shape(&shape, &start, Vec3::X, 4.0, &filter)
+            .is_none());
+    }
+
+    #[test]
+    fn points_and_shapes_find_overlapping_colliders() {
+        let (mut app, [cuboid, ball, _]) = scene();
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let filter = QueryFilter::default();
+
+        let projection = query
+            .project_point(Vec3::new(1.2, 0.0, 0.0), &filter)
+            .unwrap();
+        assert_eq!((projection.entity, projection.is_inside), (cuboid, false));
+        assert!(projection.point.abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+        let inside = query
+            .project_point(Vec3::new(3.1, 0.0, 0.0), &filter)
+            .unwrap();
+        assert_eq!((inside.entity, inside.is_inside), (ball, true));
+
+        assert_eq!(
+            query.point_intersections(Vec3::new(3.1, 0.0, 0.0), &filter),
+            [ball]
+        );
+        assert!(query
+            .point_intersections(Vec3::new(1.5, 0.0, 0.0), &filter)
+            .is_empty());
+
+        let probe = Transform::from_xyz(1.5, 0.0, 0.0);
+        let mut overlaps = query.shape_intersections(&Shape::Ball { radius: 1.2 }, &probe, &filter);
+        overlaps.sort_unstable();
+        assert_eq!(overlaps, [cuboid, ball]);
+        assert!(query
+            .shape_intersections(&Shape::Ball { radius: 0.9 }, &probe, &filter)
+            .is_empty());
+
+        let boxed = query.aabb_intersections(
+            Vec3::new(2.0, -1.0, -1.0),
+            Vec3::new(7.0, 1.0, 1.0),
+            &filter,
+        );
+        assert_eq!(boxed.len(), 2);
+        assert!(!boxed.contains(&cuboid));
+    }
+
+    #[test]
+    fn masks_skip_colliders_outside_their_layers() {
+        let (mut app, [cuboid, ball, far]) = scene();
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let (min, max) = (Vec3::splat(-10.0), Vec3::splat(10.0));
+
+        let mut all = query.aabb_intersections(min, max, &QueryFilter::default());
+        all.sort_unstable();
+        assert_eq!(all, [cuboid, ball, far]);
+        let mut default_layer =
+            query.aabb_intersections(min, max, &QueryFilter::default().mask(0b01));
+        default_layer.sort_unstable();
+        assert_eq!(default_layer, [cuboid, ball]);
+    }
+
+    #[test]
+    fn filters_exclude_entities_and_sensors() {
+        let (mut app, [cuboid, ball, _]) = scene();
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let filter = QueryFilter::default().exclude(cuboid).exclude_sensors();
+
+        let hits = query.cast_ray_all(-5.0 * Vec3::X, Vec3::X, 100.0, &filter);
+        let entities: Vec<_> = hits.iter().map(|hit| hit.entity).collect();
+        assert_eq!(entities, [ball]);
+        assert!(query
+            .point_intersections(Vec3::new(6.0, 0.0, 0.0), &filter)
+            .is_empty());
+    }
+}
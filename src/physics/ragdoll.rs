@@ -0,0 +1,330 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::{prelude::*, utils::HashMap};
+use derive_setters::Setters;
+
+use super::{
+    collider::{Collider, Shape},
+    joint::{HingeJoint, SphericalJoint},
+    motion::{Rigid, RigidBody},
+};
+
+/// Turns the skeleton of the [`SkinnedMesh`](bevy::render::mesh::skinning::SkinnedMesh)
+/// on the same entity into a [`Ragdoll`].
+///
+/// Every joint with children becomes a bone: a capsule reaching towards its children,
+/// connected to the bone of its parent by a limited [`SphericalJoint`] or [`HingeJoint`].
+/// The skeleton is read from the [`GlobalTransform`]s of its joints, so it must have been propagated.
+#[derive(Component, Debug, Clone, Setters)]
+#[setters(generate_private = false)]
+pub struct RagdollBuilder {
+    /// The combined mass of all bones, distributed by their lengths.
+    pub mass: f32,
+    /// The radius of each bone relative to its length.
+    pub thickness: f32,
+    /// The swing limit of spherical joints, in radians.
+    pub swing_limit: f32,
+    /// The twist limit of spherical joints, in radians.
+    pub twist_limit: f32,
+    pub hinges: Vec<RagdollHinge>,
+    pub mode: RagdollMode,
+}
+
+/// Connects a bone to the bone of its parent with a [`HingeJoint`], e.g. for elbows and knees.
+#[derive(Debug, Clone)]
+pub struct RagdollHinge {
+    /// The [`Name`] of the joint starting the bone.
+    pub joint: String,
+    /// The hinge axis in the local space of the joint.
+    pub axis: Vec3,
+    /// The range of the hinge angle relative to the pose the ragdoll was built in, in radians.
+    pub limits: (f32, f32),
+}
+
+/// The rigid bodies simulating a skeleton, built by a [`RagdollBuilder`].
+#[derive(Component, Debug, Clone)]
+pub struct Ragdoll {
+    pub mode: RagdollMode,
+    bones: Vec<Bone>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RagdollMode {
+    /// The bones are kinematic bodies following the animated joints,
+    /// pushing other bodies without being pushed back.
+    #[default]
+    Animated,
+    /// The bones are dynamic bodies, and the joints are posed after them.
+    /// Any animation of the joints must be stopped.
+    Simulated,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Bone {
+    pub joint: Entity,
+    /// The parent of the joint in the hierarchy.
+    pub parent: Option<Entity>,
+    pub body: Entity,
+    /// The pose of the joint in the local space of the body.
+    pub offset: Transform,
+}
+
+impl Default for RagdollBuilder {
+    fn default() -> Self {
+        Self {
+            mass: 60.0,
+            thickness: 0.15,
+            swing_limit: FRAC_PI_4,
+            twist_limit: FRAC_PI_4,
+            hinges: Vec::new(),
+            mode: RagdollMode::Animated,
+        }
+    }
+}
+
+impl RagdollBuilder {
+    pub fn hinge(mut self, joint: impl Into<String>, axis: Vec3, limits: (f32, f32)) -> Self {
+        self.hinges.push(RagdollHinge {
+            joint: joint.into(),
+            axis,
+            limits,
+        });
+        self
+    }
+
+    /// Spawns the bodies and joints of the bones, ordered so that parents come before their children.
+    #[allow(clippy::type_complexity)]
+    pub(super) fn build(
+        &self,
+        commands: &mut Commands,
+        joints: &[Entity],
+        query: &Query<(Ref<GlobalTransform>, Option<&Name>, Option<&Parent>)>,
+    ) -> Ragdoll {
+        let parents: HashMap<Entity, Entity> = joints
+            .iter()
+            .filter_map(|&joint| {
+                let (_, _, parent) = query.get(joint).ok()?;
+                Some((joint, parent?.get()))
+            })
+            .filter(|(_, parent)| joints.contains(parent))
+            .collect();
+
+        let mut order: Vec<Entity> = joints
+            .iter()
+            .copied()
+            .filter(|joint| !parents.contains_key(joint))
+            .collect();
+        let mut i = 0;
+        while i < order.len() {
+            let parent = order[i];
+            order.extend(
+                joints
+                    .iter()
+                    .filter(|joint| parents.get(*joint) == Some(&parent)),
+            );
+            i += 1;
+        }
+
+        // Each bone reaches from its joint towards the average of its children.
+        let segments: HashMap<Entity, (Vec3, Vec3)> = order
+            .iter()
+            .filter_map(|&joint| {
+                let start = query.get(joint).ok()?.0.translation();
+                let children: Vec<Vec3> = joints
+                    .iter()
+                    .filter(|child| parents.get(*child) == Some(&joint))
+                    .filter_map(|&child| Some(query.get(child).ok()?.0.translation()))
+                    .collect();
+                if children.is_empty() {
+                    return None;
+                }
+                let end = children.iter().sum::<Vec3>() / children.len() as f32;
+                (start.distance(end) > f32::EPSILON).then_some((joint, (start, end)))
+            })
+            .collect();
+        let total_length: f32 = order
+            .iter()
+            .filter_map(|joint| segments.get(joint))
+            .map(|(start, end)| start.distance(*end))
+            .sum();
+
+        let mut bodies = HashMap::new();
+        let mut bones = Vec::new();
+        for &joint in &order {
+            let (Some(&(start, end)), Ok((global, name, parent))) =
+                (segments.get(&joint), query.get(joint))
+            else {
+                continue;
+            };
+
+            let length = start.distance(end);
+            let direction = (end - start) / length;
+            let radius = self.thickness * length;
+            let pose = Transform::from_translation(0.5 * (start + end))
+                .with_rotation(Quat::from_rotation_arc(Vec3::Y, direction));
+
+            // The caps stop short of the joints, so the capsules of neighbouring bones
+            // only touch when bent beyond 120 degrees.
+            let body = commands
+                .spawn((
+                    Collider {
+                        mass: self.mass * length / total_length,
+                        shape: Shape::Capsule {
+                            radius,
+                            length: (length - 4.0 * radius).max(0.0),
+                        },
+                    },
+                    Rigid::default(),
+                    self.mode.rigid_body(),
+                    TransformBundle::from_transform(pose),
+                ))
+                .id();
+
+            let mut ancestor = parents.get(&joint);
+            while let Some(parent) = ancestor.filter(|parent| !bodies.contains_key(*parent)) {
+                ancestor = parents.get(parent);
+            }
+            if let Some((parent_body, parent_pose)) = ancestor.and_then(|parent| bodies.get(parent))
+            {
+                let local = |pose: &Transform, direction: Vec3| pose.rotation.inverse() * direction;
+                let anchors = (
+                    local(parent_pose, start - parent_pose.translation),
+                    local(&pose, start - pose.translation),
+                );
+                let hinge = self
+                    .hinges
+                    .iter()
+                    .find(|hinge| name.is_some_and(|name| name.as_str() == hinge.joint));
+
+                match hinge {
+                    Some(hinge) => {
+                        let axis = global.compute_transform().rotation * hinge.axis.normalize();
+                        let normal = direction
+                            .reject_from_normalized(axis)
+                            .try_normalize()
+                            .unwrap_or_else(|| axis.any_orthonormal_vector());
+                        commands.spawn(
+                            HingeJoint::new(*parent_body, body)
+                                .anchors(anchors)
+                                .axes((local(parent_pose, axis), local(&pose, axis)))
+                                .normals((local(parent_pose, normal), local(&pose, normal)))
+                                .limits(hinge.limits),
+                        );
+                    }
+                    None => {
+                        let normal = direction.any_orthonormal_vector();
+                        commands.spawn(
+                            SphericalJoint::new(*parent_body, body)
+                                .anchors(anchors)
+                                .axes((local(parent_pose, direction), Vec3::Y))
+                                .normals((local(parent_pose, normal), local(&pose, normal)))
+                                .swing_limit(self.swing_limit)
+                                .twist_limit(self.twist_limit),
+                        );
+                    }
+                }
+            }
+
+            bodies.insert(joint, (body, pose));
+            bones.push(Bone {
+                joint,
+                parent: parent.map(Parent::get),
+                body,
+                offset: global.reparented_to(&GlobalTransform::from(pose)),
+            });
+        }
+
+        Ragdoll {
+            mode: self.mode,
+            bones,
+        }
+    }
+}
+
+impl Ragdoll {
+    /// The bodies of all bones, e.g. for applying impulses to a simulated ragdoll.
+    #[allow(unused)]
+    pub fn bodies(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.bones.iter().map(|bone| bone.body)
+    }
+
+    /// The bones, ordered so that parents come before their children.
+    pub(super) fn bones(&self) -> &[Bone] {
+        &self.bones
+    }
+}
+
+impl RagdollMode {
+    pub(super) fn rigid_body(self) -> RigidBody {
+        match self {
+            RagdollMode::Animated => RigidBody::Kinematic,
+            RagdollMode::Simulated => RigidBody::Dynamic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::skinning::SkinnedMesh;
+
+    use super::*;
+    use crate::physics::{tests::app, PhysicsParameters};
+
+    #[test]
+    fn skeletons_become_jointed_bones() {
+        let mut app = app(PhysicsParameters::default());
+        // Joints are posed directly, as there is no transform propagation.
+        let mut joint = |name: &str, height: f32| {
+            let transform = Transform::from_xyz(0.0, height, 0.0);
+            app.world
+                .spawn((
+                    Name::new(name.to_string()),
+                    TransformBundle {
+                        local: transform,
+                        global: transform.into(),
+                    },
+                ))
+                .id()
+        };
+        let joints = vec![joint("hip", 2.0), joint("knee", 1.0), joint("foot", 0.0)];
+        app.world.entity_mut(joints[1]).set_parent(joints[0]);
+        app.world.entity_mut(joints[2]).set_parent(joints[1]);
+        let skeleton = app
+            .world
+            .spawn((
+                SkinnedMesh {
+                    inverse_bindposes: default(),
+                    joints,
+                },
+                RagdollBuilder::default().hinge("knee", Vec3::X, (-2.0, 0.0)),
+            ))
+            .id();
+
+        // The skeleton is only read once its transforms are no longer freshly added.
+        app.update();
+        assert!(app.world.get::<Ragdoll>(skeleton).is_none());
+        app.update();
+
+        let ragdoll = app.world.get::<Ragdoll>(skeleton).unwrap().clone();
+        let bodies: Vec<_> = ragdoll.bodies().collect();
+        assert_eq!(bodies.len(), 2);
+        for (body, height) in bodies.into_iter().zip([1.5, 0.5]) {
+            let entity = app.world.entity(body);
+            assert_eq!(entity.get::<RigidBody>(), Some(&RigidBody::Kinematic));
+            assert_eq!(entity.get::<Collider>().unwrap().mass, 30.0);
+            let translation = entity.get::<Transform>().unwrap().translation;
+            assert!(translation.abs_diff_eq(Vec3::new(0.0, height, 0.0), 1e-5));
+        }
+
+        let mut hinges = app.world.query::<&HingeJoint>();
+        let hinges: Vec<_> = hinges.iter(&app.world).collect();
+        assert!(matches!(hinges[..], [hinge] if hinge.limits == (-2.0, 0.0)));
+        assert_eq!(
+            app.world
+                .query::<&SphericalJoint>()
+                .iter(&app.world)
+                .count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use bevy::prelude::*;
+use derive_setters::Setters;
+
+use super::query::{QueryFilter, SpatialQuery};
+
+/// A dynamic chassis carried by raycast wheels.
+///
+/// Each wheel casts a ray down from its anchor to find the ground and pushes the chassis up with a damped spring.
+/// Tyre friction then acts at the contact points. All forces go through the
+/// [`ExternalForce`](super::force::ExternalForce) and [`ExternalTorque`](super::force::ExternalTorque)
+/// of the chassis, which are inserted if missing and made non-persistent.
+///
+/// The chassis faces its local negative Z-axis with its local Y-axis pointing up.
+#[derive(Component, Debug, Clone, Setters)]
+#[setters(generate_private = false)]
+pub struct Vehicle {
+    pub wheels: Vec<Wheel>,
+    /// The torque on each driven wheel at full throttle.
+    pub engine_torque: f32,
+    /// The torque on each wheel at full brake.
+    pub brake_torque: f32,
+    /// The steering angle of steered wheels at full lock, in radians.
+    pub max_steering_angle: f32,
+}
+
+#[derive(Debug, Clone, Copy, Setters)]
+#[setters(generate_private = false)]
+pub struct Wheel {
+    /// The top of the suspension in the local space of the chassis.
+    pub anchor: Vec3,
+    pub radius: f32,
+    /// Determines the moment of inertia of the wheel, and thereby how quickly the engine spins it up.
+    pub mass: f32,
+    /// The length of the unloaded suspension, from the anchor to the center of the wheel.
+    pub rest_length: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+    pub steered: bool,
+    pub driven: bool,
+    /// The grip along the rolling direction.
+    pub longitudinal_friction: FrictionCurve,
+    /// The grip across the rolling direction.
+    pub lateral_friction: FrictionCurve,
+    compression: f32,
+    spin: f32,
+    steering_angle: f32,
+}
+
+/// Maps the slip speed between tyre and ground to a friction coefficient.
+///
+/// The coefficient rises linearly up to its peak and then blends to its sliding value,
+/// so tyres grip best while slipping slightly.
+#[derive(Debug, Clone, Copy)]
+pub struct FrictionCurve {
+    pub peak_slip: f32,
+    pub peak: f32,
+    pub sliding_slip: f32,
+    pub sliding: f32,
+}
+
+/// The controls of a [`Vehicle`].
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct VehicleInput {
+    /// From -1 for full reverse to 1 for full throttle.
+    pub throttle: f32,
+    /// From 0 to 1.
+    pub brake: f32,
+    /// From -1 for full left to 1 for full right.
+    pub steering: f32,
+}
+
+impl Vehicle {
+    pub fn new(wheels: Vec<Wheel>) -> Self {
+        Self {
+            wheels,
+            engine_torque: 3.0,
+            brake_torque: 4.0,
+            max_steering_angle: 0.5,
+        }
+    }
+
+    /// Updates the wheels for the coming step and returns the forces on the chassis,
+    /// each paired with the world-space point it acts at.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn forces(
+        &mut self,
+        input: &VehicleInput,
+        transform: &Transform,
+        velocity: Vec3,
+        angular_velocity: Vec3,
+        mass: f32,
+        query: &SpatialQuery,
+        filter: &QueryFilter,
+        dt: f32,
+    ) -> Vec<(Vec3, Vec3)> {
+        let up = transform.rotation * Vec3::Y;
+        let steering_angle = input.steering.clamp(-1.0, 1.0) * self.max_steering_angle;
+
+        let contacts: Vec<_> = self
+            .wheels
+            .iter_mut()
+            .map(|wheel| {
+                wheel.steering_angle = if wheel.steered { steering_angle } else { 0.0 };
+                let reach = wheel.rest_length + wheel.radius;
+                let Some(hit) =
+                    query.cast_ray(transform.transform_point(wheel.anchor), -up, reach, filter)
+                else {
+                    wheel.compression = 0.0;
+                    return None;
+                };
+
+                let compression = reach - hit.distance;
+                let compression_speed = (compression - wheel.compression) / dt;
+                wheel.compression = compression;
+                let load =
+                    (wheel.stiffness * compression + wheel.damping * compression_speed).max(0.0);
+                Some((hit.point, hit.normal, load))
+            })
+            .collect();
+
+        // Every grounded wheel carries an equal share of the chassis when limiting friction.
+        let grounded = contacts.iter().flatten().count();
+        let mass_share = mass / grounded.max(1) as f32;
+
+        let mut forces = Vec::new();
+        for (wheel, contact) in self.wheels.iter_mut().zip(contacts) {
+            let inertia = 0.5 * wheel.mass * wheel.radius * wheel.radius;
+            if wheel.driven {
+                wheel.spin += dt * input.throttle.clamp(-1.0, 1.0) * self.engine_torque / inertia;
+            }
+            let braking = dt * input.brake.clamp(0.0, 1.0) * self.brake_torque / inertia;
+            wheel.spin -= wheel.spin.clamp(-braking, braking);
+
+            let Some((point, normal, load)) = contact else {
+                continue;
+            };
+
+            let heading =
+                transform.rotation * Quat::from_rotation_y(-wheel.steering_angle) * Vec3::NEG_Z;
+            let forward = heading.reject_from_normalized(normal).normalize_or_zero();
+            let side = forward.cross(normal);
+            let velocity = velocity + angular_velocity.cross(point - transform.translation);
+
+            // Neither friction force may reverse the slip within a single step.
+            let slip = wheel.spin * wheel.radius - velocity.dot(forward);
+            let coupling = dt * (wheel.radius * wheel.radius / inertia + mass_share.recip());
+            let longitudinal = slip.signum()
+                * (wheel.longitudinal_friction.evaluate(slip.abs()) * load)
+                    .min(slip.abs() / coupling);
+            wheel.spin -= dt * longitudinal * wheel.radius / inertia;
+
+            let lateral_slip = velocity.dot(side);
+            let lateral = -lateral_slip.signum()
+                * (wheel.lateral_friction.evaluate(lateral_slip.abs()) * load)
+                    .min(mass_share * lateral_slip.abs() / dt);
+
+            forces.push((load * up + longitudinal * forward + lateral * side, point));
+        }
+        forces
+    }
+}
+
+impl Wheel {
+    pub fn new(anchor: Vec3) -> Self {
+        Self {
+            anchor,
+            radius: 0.3,
+            mass: 0.2,
+            rest_length: 0.4,
+            stiffness: 60.0,
+            damping: 6.0,
+            steered: false,
+            driven: false,
+            longitudinal_friction: FrictionCurve::default(),
+            lateral_friction: FrictionCurve::default(),
+            compression: 0.0,
+            spin: 0.0,
+            steering_angle: 0.0,
+        }
+    }
+
+    /// The world-space anchor, wheel center and axle direction, for drawing.
+    pub(super) fn pose(&self, transform: &Transform) -> (Vec3, Vec3, Vec3) {
+        let anchor = transform.transform_point(self.anchor);
+        let up = transform.rotation * Vec3::Y;
+        let center = anchor - (self.rest_length - self.compression) * up;
+        let axle = transform.rotation * Quat::from_rotation_y(-self.steering_angle) * Vec3::X;
+        (anchor, center, axle)
+    }
+}
+
+impl Default for FrictionCurve {
+    fn default() -> Self {
+        Self {
+            peak_slip: 0.2,
+            peak: 1.0,
+            sliding_slip: 1.0,
+            sliding: 0.7,
+        }
+    }
+}
+
+impl FrictionCurve {
+    pub fn evaluate(&self, slip: f32) -> f32 {
+        if slip < self.peak_slip {
+            self.peak * slip / self.peak_slip
+        } else if slip < self.sliding_slip {
+            let t = (slip - self.peak_slip) / (self.sliding_slip - self.peak_slip);
+            self.peak + t * (self.sliding - self.peak)
+        } else {
+            self.sliding
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::physics::{
+        collider::{Collider, Shape},
+        force::{ExternalForce, ExternalTorque},
+        motion::{Linear, Rigid},
+        tests::{app, ground},
+        PhysicsParameters,
+    };
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// An app with a ground plane through the origin, known to the broad phase.
+    fn flat() -> App {
+        let mut app = app(PhysicsParameters::default());
+        ground(&mut app);
+        app.update();
+        app
+    }
+
+    #[test]
+    fn friction_peaks_before_sliding() {
+        let curve = FrictionCurve::default();
+        assert_eq!(curve.evaluate(0.0), 0.0);
+        assert!((curve.evaluate(0.1) - 0.5).abs() < 1e-6);
+        assert!((curve.evaluate(0.2) - 1.0).abs() < 1e-6);
+        assert!((curve.evaluate(0.6) - 0.85).abs() < 1e-6);
+        assert_eq!(curve.evaluate(5.0), 0.7);
+    }
+
+    #[test]
+    fn compressed_suspensions_carry_the_chassis() {
+        let mut app = flat();
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let mut vehicle = Vehicle::new(vec![Wheel::new(Vec3::ZERO)]);
+        let mut forces = |height: f32| {
+            vehicle.forces(
+                &VehicleInput::default(),
+                &Transform::from_xyz(0.0, height, 0.0),
+                Vec3::ZERO,
+                Vec3::ZERO,
+                1.0,
+                &query,
+                &QueryFilter::default(),
+                DT,
+            )
+        };
+
+        // The wheel reaches 0.7 below its anchor, so it is compressed by 0.2.
+        forces(0.5);
+        let [(force, point)] = forces(0.5)[..] else {
+            panic!("expected a single wheel force");
+        };
+        assert!(force.abs_diff_eq(60.0 * 0.2 * Vec3::Y, 1e-3));
+        assert!(point.abs_diff_eq(Vec3::ZERO, 1e-5));
+
+        assert!(forces(1.0).is_empty());
+    }
+
+    #[test]
+    fn throttle_drives_the_chassis_forwards() {
+        let mut app = flat();
+        let mut state = SystemState::<SpatialQuery>::new(&mut app.world);
+        let query = state.get(&app.world);
+        let mut vehicle = Vehicle::new(vec![Wheel::new(Vec3::ZERO).driven(true)]);
+        let input = VehicleInput {
+            throttle: 1.0,
+            ..default()
+        };
+        let transform = Transform::from_xyz(0.0, 0.5, 0.0);
+
+        let mut force = Vec3::ZERO;
+        for _ in 0..2 {
+            let forces = vehicle.forces(
+                &input,
+                &transform,
+                Vec3::ZERO,
+                Vec3::ZERO,
+                1.0,
+                &query,
+                &QueryFilter::default(),
+                DT,
+            );
+            force = forces[0].0;
+        }
+        assert!(force.z < 0.0);
+        assert!(force.x.abs() < 1e-6);
+        // The tyre grips at most with its peak friction times the load.
+        assert!(-force.z <= force.y + 1e-4);
+    }
+
+    #[test]
+    fn vehicles_settle_on_their_suspension() {
+        for persistent in [None, Some(true)] {
+            let mut app = flat();
+            let wheels = [(-0.5, -0.8), (0.5, -0.8), (-0.5, 0.8), (0.5, 0.8)]
+                .map(|(x, z)| Wheel::new(Vec3::new(x, -0.2, z)));
+            let mut chassis = app.world.spawn((
+                Collider {
+                    mass: 1.0,
+                    shape: Shape::Cuboid {
+                        half_extents: Vec3::new(0.5, 0.2, 1.0),
+                    },
+                },
+                Rigid::default(),
+                Vehicle::new(wheels.into()),
+                VehicleInput::default(),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.8, 0.0)),
+            ));
+            if let Some(persistent) = persistent {
+                chassis.insert((
+                    ExternalForce::default().persistent(persistent),
+                    ExternalTorque::default().persistent(persistent),
+                ));
+            }
+            let chassis = chassis.id();
+            for _ in 0..300 {
+                app.update();
+            }
+
+            // The four springs carry the weight of 10 N at a compression of 10 / 240.
+            let entity = app.world.entity(chassis);
+            let height = entity.get::<Transform>().unwrap().translation.y;
+            assert!((height - (0.9 - 10.0 / 240.0)).abs() < 0.01);
+            assert!(entity.get::<Linear>().unwrap().velocity.length() < 0.01);
+            assert!(!entity.get::<ExternalForce>().unwrap().persistent);
+        }
+    }
+}